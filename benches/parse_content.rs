@@ -0,0 +1,34 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use prk_mdgen::parser::{MdPatternType, parse_content, parse_content_with_extensions};
+use std::hint::black_box;
+
+/// Builds a large hash-pattern document with `files` file blocks.
+fn large_document(files: usize) -> String {
+    let mut md = String::new();
+    for i in 0..files {
+        md.push_str(&format!(
+            "### src/module_{i}.rs\n```rust\npub fn function_{i}() -> usize {{\n    {i}\n}}\n```\n\n"
+        ));
+    }
+    md
+}
+
+fn bench_parse_content(c: &mut Criterion) {
+    let md = large_document(5_000);
+    c.bench_function("parse_content auto", |b| {
+        b.iter(|| parse_content(black_box(&md), None))
+    });
+    c.bench_function("parse_content forced hash", |b| {
+        b.iter(|| parse_content(black_box(&md), Some(MdPatternType::HashMarker)))
+    });
+    // Repeated calls with the same extension set reuse the compiled regexes.
+    let extensions = vec!["rs".to_string(), "py".to_string()];
+    c.bench_function("parse_content_with_extensions raw", |b| {
+        b.iter(|| {
+            parse_content_with_extensions(black_box(&md), Some(MdPatternType::Raw), &extensions)
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse_content);
+criterion_main!(benches);
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+const INDEX_FILE: &str = "index.json";
+
+/// One cached file section, keyed by relative path in the index.
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    mtime_ns: u64,
+    size: u64,
+    fingerprint: String,
+    hash: Option<String>,
+    #[serde(default)]
+    modules: Option<String>,
+    /// Blob file holding the rendered block; `None` when the file was filtered out.
+    blob: Option<String>,
+}
+
+/// What extraction needs to emit a file without re-reading or re-rendering it.
+pub struct CachedSection {
+    pub hash: Option<String>,
+    /// `--module-map` annotation of a Rust file.
+    pub modules: Option<String>,
+    pub block: Option<String>,
+}
+
+/// On-disk cache of rendered extract sections: a JSON index plus one blob per file.
+///
+/// Entries are reused only when the file's mtime and size are unchanged and the
+/// rendering settings (the `fingerprint`) match the ones used to produce them.
+pub struct ExtractCache {
+    dir: PathBuf,
+    fingerprint: String,
+    index: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl ExtractCache {
+    /// Opens (or creates) the cache in `dir`. A missing or unreadable index starts empty.
+    pub fn open(dir: &Path, fingerprint: String) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create cache directory: {:?}", dir))?;
+        let index = fs::read_to_string(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            fingerprint,
+            index: Mutex::new(index),
+        })
+    }
+
+    /// Returns the cached section for `rel` if the file is unchanged since it was stored.
+    pub fn get(&self, rel: &str, meta: &Metadata) -> Option<CachedSection> {
+        let entry = self.index.lock().unwrap().get(rel).cloned()?;
+        if entry.mtime_ns != mtime_ns(meta)
+            || entry.size != meta.len()
+            || entry.fingerprint != self.fingerprint
+        {
+            return None;
+        }
+        let block = match &entry.blob {
+            Some(blob) => Some(fs::read_to_string(self.dir.join(blob)).ok()?),
+            None => None,
+        };
+        Some(CachedSection {
+            hash: entry.hash,
+            modules: entry.modules,
+            block,
+        })
+    }
+
+    /// Stores the section for `rel`, writing its block to a blob file.
+    pub fn put(&self, rel: &str, meta: &Metadata, section: &CachedSection) -> Result<()> {
+        let blob = match &section.block {
+            Some(block) => {
+                let digest = Sha256::digest(rel.as_bytes());
+                let name: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+                let name = format!("{}.md", name);
+                fs::write(self.dir.join(&name), block)
+                    .with_context(|| format!("failed to write cache blob for {}", rel))?;
+                Some(name)
+            }
+            None => None,
+        };
+        let entry = CacheEntry {
+            mtime_ns: mtime_ns(meta),
+            size: meta.len(),
+            fingerprint: self.fingerprint.clone(),
+            hash: section.hash.clone(),
+            modules: section.modules.clone(),
+            blob,
+        };
+        self.index.lock().unwrap().insert(rel.to_string(), entry);
+        Ok(())
+    }
+
+    /// Persists the index so the next run can reuse the stored sections.
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&*self.index.lock().unwrap())?;
+        let path = self.dir.join(INDEX_FILE);
+        fs::write(&path, json).with_context(|| format!("failed to write cache index: {:?}", path))
+    }
+}
+
+fn mtime_ns(meta: &Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
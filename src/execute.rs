@@ -1,57 +1,506 @@
-use std::{fs, process::Command, path::Path};
+use crate::semaphore::Semaphore;
+use crate::task_log::TaskLog;
+use lazy_static::lazy_static;
+use prk_mdgen::file_gen::ProjectKind;
+use regex::bytes::Regex;
+use std::{fs, io, path::Path, process::Command};
 
-pub fn execute_project_if_needed(project_dir: &Path, output_dir: &Path) -> std::io::Result<()> {
-    let main_rs = project_dir.join("src/main.rs");
-    let cargo_toml = project_dir.join("Cargo.toml");
+/// Captured result of an external command.
+pub struct RunOutput {
+    pub success: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Abstraction over spawning external commands, so execution can be tested
+/// without invoking cargo.
+pub trait CommandRunner: Sync {
+    fn run(&self, program: &str, args: &[&str], dir: &Path) -> io::Result<RunOutput>;
+}
+
+/// Runs commands as real child processes, asking cargo not to colour its output.
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str], dir: &Path) -> io::Result<RunOutput> {
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(dir)
+            .env("CARGO_TERM_COLOR", "never")
+            .output()?;
+        Ok(RunOutput {
+            success: output.status.success(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+}
+
+/// Bounds how many projects are executed concurrently, independently of how
+/// many Markdown files are parsed in parallel.
+pub struct ExecPool {
+    permits: Semaphore,
+}
+
+impl ExecPool {
+    pub fn new(jobs: usize) -> Self {
+        Self {
+            permits: Semaphore::new(jobs),
+        }
+    }
+
+    /// Executes the project once a slot in the pool is free; see
+    /// [`execute_project_if_needed`].
+    pub fn execute(
+        &self,
+        project_dir: &Path,
+        output_dir: &Path,
+        runner: &dyn CommandRunner,
+        log: &TaskLog,
+    ) -> io::Result<bool> {
+        let _permit = self.permits.acquire();
+        execute_project_if_needed(project_dir, output_dir, runner, log)
+    }
 
-    if !cargo_toml.exists() {
-        eprintln!("No Cargo.toml found at {:?}, skipping execution.", cargo_toml);
-        return Ok(());
+    /// Runs [`verify_compiles`] once a slot in the pool is free.
+    pub fn verify_compiles(
+        &self,
+        project_dir: &Path,
+        runner: &dyn CommandRunner,
+        log: &TaskLog,
+    ) -> io::Result<bool> {
+        let _permit = self.permits.acquire();
+        verify_compiles(project_dir, runner, log)
     }
+}
+
+/// Builds and tests `project_dir` through `runner` with the toolchain matching its
+/// [`ProjectKind`], saving the captured output into `output_dir`. Returns whether
+/// every command succeeded; projects without a manifest are skipped and pass.
+pub fn execute_project_if_needed(
+    project_dir: &Path,
+    output_dir: &Path,
+    runner: &dyn CommandRunner,
+    log: &TaskLog,
+) -> io::Result<bool> {
+    let Some(kind) = ProjectKind::from_dir(project_dir) else {
+        log.warn(format!(
+            "No Cargo.toml or package.json found in {:?}, skipping execution.",
+            project_dir
+        ));
+        return Ok(true);
+    };
 
     // Ensure the output directory exists
     fs::create_dir_all(output_dir)?;
 
+    match kind {
+        ProjectKind::Rust => execute_cargo(project_dir, output_dir, runner, log),
+        ProjectKind::Node => execute_npm(project_dir, output_dir, runner, log),
+        ProjectKind::Flutter => {
+            log.warn(format!(
+                "Flutter projects are not executed, skipping {:?}.",
+                project_dir
+            ));
+            Ok(true)
+        }
+    }
+}
+
+/// Runs `cargo run` (for binaries) and `cargo test`, logging to `run_output.log`
+/// and `test_output.log`. Returns whether both succeeded.
+fn execute_cargo(
+    project_dir: &Path,
+    output_dir: &Path,
+    runner: &dyn CommandRunner,
+    log: &TaskLog,
+) -> io::Result<bool> {
+    let main_rs = project_dir.join("src/main.rs");
+    let cargo_toml = project_dir.join("Cargo.toml");
+
     // check the contents of Cargo.toml
-    let cargo_toml_content = fs::read_to_string(&cargo_toml).unwrap_or(String::new());
+    let cargo_toml_content = fs::read_to_string(&cargo_toml).unwrap_or_default();
 
     let if_bin = cargo_toml_content.contains("[[bin]]");
+    let mut success = true;
 
     // Run `cargo run` if main.rs is present
-    if main_rs.exists() || if_bin  {
+    if main_rs.exists() || if_bin {
         let output_file = output_dir.join("run_output.log");
-        println!("Executing `cargo run` for {:?}", project_dir);
+        log.info(format!("Executing `cargo run` for {:?}", project_dir));
 
-        let output = Command::new("cargo")
-            .arg("run")
-            .current_dir(project_dir)
-            .output()?;
+        let output = runner.run("cargo", &["run"], project_dir)?;
+        fs::write(&output_file, combined_output(&output))?;
+        success &= output.success;
+    }
 
-        let combined_output = format!(
-            "[STDOUT]\n{}\n[STDERR]\n{}",
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
-        );
+    // Run `cargo test`
+    let output_file = output_dir.join("test_output.log");
+    log.info(format!("Executing `cargo test` for {:?}", project_dir));
+
+    let output = runner.run("cargo", &["test"], project_dir)?;
+    fs::write(&output_file, combined_output(&output))?;
+
+    Ok(success && output.success)
+}
 
-        fs::write(&output_file, combined_output)?;
+/// Runs `npm install` then `npm test`, logging both to `npm_output.txt`. Returns
+/// whether both succeeded.
+fn execute_npm(
+    project_dir: &Path,
+    output_dir: &Path,
+    runner: &dyn CommandRunner,
+    log: &TaskLog,
+) -> io::Result<bool> {
+    let mut combined = String::new();
+    let mut success = true;
+    for args in [["install"], ["test"]] {
+        log.info(format!("Executing `npm {}` for {:?}", args[0], project_dir));
+        let output = runner.run("npm", &args, project_dir)?;
+        combined.push_str(&format!(
+            "[npm {}]\n{}\n",
+            args[0],
+            combined_output(&output)
+        ));
+        success &= output.success;
     }
+    fs::write(output_dir.join("npm_output.txt"), combined)?;
+    Ok(success)
+}
 
-    // Run `cargo test` 
-        let output_file = output_dir.join("test_output.log");
-        println!("Executing `cargo test` for {:?}", project_dir);
+/// Runs `cargo generate-lockfile` in a Rust project and drops `/Cargo.lock` from its
+/// `.gitignore` so the lockfile is kept. Non-Rust projects are left alone; failures
+/// only produce a warning.
+pub fn generate_lockfile(project_dir: &Path, runner: &dyn CommandRunner, log: &TaskLog) {
+    if ProjectKind::from_dir(project_dir) != Some(ProjectKind::Rust) {
+        return;
+    }
+    match runner.run("cargo", &["generate-lockfile"], project_dir) {
+        Ok(output) if output.success => {}
+        Ok(output) => {
+            log.warn(format!(
+                "`cargo generate-lockfile` failed in {:?}: {}",
+                project_dir,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+            return;
+        }
+        Err(err) => {
+            log.warn(format!("Skipping lockfile for {:?}: {}", project_dir, err));
+            return;
+        }
+    }
+    let gitignore = project_dir.join(".gitignore");
+    if let Ok(content) = fs::read_to_string(&gitignore) {
+        let kept: String = content
+            .lines()
+            .filter(|line| line.trim() != "/Cargo.lock")
+            .map(|line| format!("{}\n", line))
+            .collect();
+        if let Err(err) = fs::write(&gitignore, kept) {
+            log.warn(format!("Could not update {:?}: {}", gitignore, err));
+        }
+    }
+}
 
-        let output = Command::new("cargo")
-            .arg("test")
-            .current_dir(project_dir)
-            .output()?;
+/// Runs `cargo check` in a Rust project, saving the compiler output to
+/// `check_output.log`. Returns whether it compiled; non-Rust projects pass.
+pub fn verify_compiles(
+    project_dir: &Path,
+    runner: &dyn CommandRunner,
+    log: &TaskLog,
+) -> io::Result<bool> {
+    if ProjectKind::from_dir(project_dir) != Some(ProjectKind::Rust) {
+        return Ok(true);
+    }
+    log.info(format!("Executing `cargo check` for {:?}", project_dir));
+    let output = runner.run("cargo", &["check"], project_dir)?;
+    fs::write(
+        project_dir.join("check_output.log"),
+        combined_output(&output),
+    )?;
+    if !output.success {
+        log.error(format!(
+            "`cargo check` failed in {:?}:\n{}",
+            project_dir,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.success)
+}
+
+/// Turns `project_dir` into a git repository with the generated files as its first
+/// commit. Missing git or a failing git command only produces a warning.
+pub fn git_init_project(project_dir: &Path, runner: &dyn CommandRunner, log: &TaskLog) {
+    let steps: [&[&str]; 3] = [
+        &["init", "-q"],
+        &["add", "-A"],
+        &["commit", "-q", "-m", "Initial commit"],
+    ];
+    for args in steps {
+        match runner.run("git", args, project_dir) {
+            Ok(output) if output.success => {}
+            Ok(output) => {
+                log.warn(format!(
+                    "`git {}` failed in {:?}: {}",
+                    args.join(" "),
+                    project_dir,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+                return;
+            }
+            Err(err) => {
+                log.warn(format!("Skipping git init for {:?}: {}", project_dir, err));
+                return;
+            }
+        }
+    }
+}
+
+/// Both streams of `output` as plain text for the saved logs, with ANSI escape
+/// sequences removed in case a tool colours its output anyway.
+fn combined_output(output: &RunOutput) -> String {
+    format!(
+        "[STDOUT]\n{}\n[STDERR]\n{}",
+        String::from_utf8_lossy(&strip_ansi(&output.stdout)),
+        String::from_utf8_lossy(&strip_ansi(&output.stderr))
+    )
+}
+
+/// Removes ANSI CSI sequences (colours, cursor movement) and OSC sequences (titles,
+/// hyperlinks) from `bytes`.
+fn strip_ansi(bytes: &[u8]) -> Vec<u8> {
+    lazy_static! {
+        static ref ANSI_REGEX: Regex =
+            Regex::new(r"\x1b(?:\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)?)").unwrap();
+    }
+    ANSI_REGEX.replace_all(bytes, &b""[..]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Records every invocation and the peak number of concurrent runs.
+    #[derive(Default)]
+    struct MockRunner {
+        /// Make every command fail with a compiler-style error on stderr.
+        fail: bool,
+        /// What every command prints on stdout.
+        stdout: Vec<u8>,
+        calls: Mutex<Vec<String>>,
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl CommandRunner for MockRunner {
+        fn run(&self, program: &str, args: &[&str], _dir: &Path) -> io::Result<RunOutput> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", program, args.join(" ")));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(RunOutput {
+                success: !self.fail,
+                stdout: self.stdout.clone(),
+                stderr: if self.fail {
+                    b"error[E0425]: cannot find value `x`".to_vec()
+                } else {
+                    Vec::new()
+                },
+            })
+        }
+    }
+
+    fn rust_project(root: &Path, name: &str) -> std::path::PathBuf {
+        let dir = root.join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_exec_pool_bounds_concurrency() {
+        let tmp = tempfile::tempdir().unwrap();
+        let projects: Vec<_> = (0..6)
+            .map(|i| rust_project(tmp.path(), &format!("p{}", i)))
+            .collect();
+        let runner = MockRunner::default();
+        let pool = ExecPool::new(2);
+
+        projects.par_iter().for_each(|dir| {
+            pool.execute(dir, dir, &runner, &TaskLog::new(false))
+                .unwrap();
+        });
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 12);
+        assert!(runner.peak.load(Ordering::SeqCst) <= 2);
+        assert!(projects[0].join("run_output.log").exists());
+        assert!(projects[0].join("test_output.log").exists());
+    }
+
+    #[test]
+    fn test_exec_pool_bounds_compile_checks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let projects: Vec<_> = (0..4)
+            .map(|i| rust_project(tmp.path(), &format!("c{}", i)))
+            .collect();
+        let runner = MockRunner::default();
+        let pool = ExecPool::new(1);
+
+        projects.par_iter().for_each(|dir| {
+            assert!(
+                pool.verify_compiles(dir, &runner, &TaskLog::new(false))
+                    .unwrap()
+            );
+        });
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 4);
+        assert_eq!(runner.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_node_project_runs_npm() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("web");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.json"), "{\"name\": \"web\"}").unwrap();
+        assert_eq!(ProjectKind::from_dir(&dir), Some(ProjectKind::Node));
+
+        let runner = MockRunner::default();
+        execute_project_if_needed(&dir, &dir, &runner, &TaskLog::new(false)).unwrap();
+
+        assert_eq!(*runner.calls.lock().unwrap(), ["npm install", "npm test"]);
+        let log = fs::read_to_string(dir.join("npm_output.txt")).unwrap();
+        assert!(log.contains("[npm install]") && log.contains("[npm test]"));
+        assert!(!dir.join("test_output.log").exists());
+    }
+
+    #[test]
+    fn test_failing_commands_report_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = rust_project(tmp.path(), "broken");
+        let runner = MockRunner {
+            fail: true,
+            ..Default::default()
+        };
+        assert!(!execute_project_if_needed(&dir, &dir, &runner, &TaskLog::new(false)).unwrap());
+        assert_eq!(*runner.calls.lock().unwrap(), ["cargo run", "cargo test"]);
+        let log = fs::read_to_string(dir.join("test_output.log")).unwrap();
+        assert!(log.contains("error[E0425]"));
+        assert!(
+            execute_project_if_needed(&dir, &dir, &MockRunner::default(), &TaskLog::new(false))
+                .unwrap()
+        );
+
+        let node = tmp.path().join("node");
+        fs::create_dir_all(&node).unwrap();
+        fs::write(node.join("package.json"), "{}").unwrap();
+        assert!(!execute_project_if_needed(&node, &node, &runner, &TaskLog::new(false)).unwrap());
+    }
 
-        let combined_output = format!(
-            "[STDOUT]\n{}\n[STDERR]\n{}",
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
+    #[test]
+    fn test_generate_lockfile() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = rust_project(tmp.path(), "locked");
+        fs::write(dir.join(".gitignore"), "\n/target\n/Cargo.lock\n").unwrap();
+
+        let runner = MockRunner::default();
+        generate_lockfile(&dir, &runner, &TaskLog::new(false));
+        assert_eq!(*runner.calls.lock().unwrap(), ["cargo generate-lockfile"]);
+        assert_eq!(
+            fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            "\n/target\n"
         );
 
-        fs::write(&output_file, combined_output)?;
+        let node = tmp.path().join("node");
+        fs::create_dir_all(&node).unwrap();
+        fs::write(node.join("package.json"), "{}").unwrap();
+        let runner = MockRunner::default();
+        generate_lockfile(&node, &runner, &TaskLog::new(false));
+        assert!(runner.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_saved_output_has_no_ansi_escapes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = rust_project(tmp.path(), "colored");
+        let runner = MockRunner {
+            stdout: b"\x1b[1m\x1b[32m   Compiling\x1b[0m demo\n\x1b]8;;https://x\x07link\x1b]8;;\x07 ok\n"
+                .to_vec(),
+            ..Default::default()
+        };
+        execute_project_if_needed(&dir, &dir, &runner, &TaskLog::new(false)).unwrap();
+
+        for log in ["run_output.log", "test_output.log"] {
+            let saved = fs::read_to_string(dir.join(log)).unwrap();
+            assert!(!saved.contains('\x1b'), "{}: {:?}", log, saved);
+            assert!(saved.contains("   Compiling demo\nlink ok\n"));
+        }
+
+        if Command::new("sh").arg("-c").arg("true").output().is_ok() {
+            let output = SystemRunner
+                .run("sh", &["-c", "printf %s \"$CARGO_TERM_COLOR\""], tmp.path())
+                .unwrap();
+            assert_eq!(output.stdout, b"never");
+        }
+    }
+
+    #[test]
+    fn test_verify_compiles() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = rust_project(tmp.path(), "checked");
+
+        let runner = MockRunner::default();
+        assert!(verify_compiles(&dir, &runner, &TaskLog::new(false)).unwrap());
+        assert_eq!(*runner.calls.lock().unwrap(), ["cargo check"]);
+
+        let runner = MockRunner {
+            fail: true,
+            ..Default::default()
+        };
+        assert!(!verify_compiles(&dir, &runner, &TaskLog::new(false)).unwrap());
+        assert_eq!(*runner.calls.lock().unwrap(), ["cargo check"]);
+        let log = fs::read_to_string(dir.join("check_output.log")).unwrap();
+        assert!(log.contains("error[E0425]"));
+
+        let node = tmp.path().join("node");
+        fs::create_dir_all(&node).unwrap();
+        fs::write(node.join("package.json"), "{}").unwrap();
+        let runner = MockRunner::default();
+        assert!(verify_compiles(&node, &runner, &TaskLog::new(false)).unwrap());
+        assert!(runner.calls.lock().unwrap().is_empty());
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_git_init_project() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = rust_project(tmp.path(), "repo");
+
+        let runner = MockRunner::default();
+        git_init_project(&dir, &runner, &TaskLog::new(false));
+        assert_eq!(
+            *runner.calls.lock().unwrap(),
+            [
+                "git init -q",
+                "git add -A",
+                "git commit -q -m Initial commit"
+            ]
+        );
+
+        if Command::new("git").arg("--version").output().is_err() {
+            return;
+        }
+        git_init_project(&dir, &SystemRunner, &TaskLog::new(false));
+        assert!(dir.join(".git").is_dir());
+    }
+}
@@ -1,6 +1,9 @@
 use std::fs;
 
-pub fn generate_sample_md() -> std::io::Result<()> {
+/// Default `--wrap-width` for the generated sample and prompt files.
+pub const DEFAULT_WRAP_WIDTH: usize = 80;
+
+pub fn generate_sample_md(wrap_width: usize) -> std::io::Result<()> {
     let sample_content = r#"
 <code path="Cargo.toml">
 [package]
@@ -15,12 +18,19 @@ fn main() {
     println!("Hello, sample project!");
 }
 "#;
-    fs::write("sample.md", sample_content.trim_start())?;
-    println!("Generated sample.md");
+    fs::write("sample.md", reflow(sample_content.trim_start(), wrap_width))?;
+    log::info!("Generated sample.md");
+    Ok(())
+}
+
+pub fn generate_prompt_md(wrap_width: usize) -> std::io::Result<()> {
+    fs::write("prompt.md", prompt_markdown(wrap_width))?;
+    log::info!("Generated prompt.md");
     Ok(())
 }
 
-pub fn generate_prompt_md() -> std::io::Result<()> {
+/// The prompt template, reflowed to `wrap_width`.
+fn prompt_markdown(wrap_width: usize) -> String {
     let prompt_content = r#"
 
 Expected Format:
@@ -78,8 +88,122 @@ fn main() {
     println!("Hello, sample project!");
 }
 "#;
+    reflow(prompt_content.trim_start(), wrap_width)
+}
 
-    fs::write("prompt.md", prompt_content.trim_start())?;
-    println!("Generated prompt.md");
-    Ok(())
+/// Tracks, line by line, whether a line belongs to a fenced code block or a
+/// `<code ...>` ... `</code>` span.
+#[derive(Default)]
+struct CodeSpans {
+    in_fence: bool,
+    in_tag: bool,
+}
+
+impl CodeSpans {
+    /// Whether `line`, the next line of the text, is code (fences and tags included).
+    fn is_code(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if self.in_tag {
+            self.in_tag = !line.contains("</code>");
+            return true;
+        }
+        if trimmed.starts_with("```") {
+            self.in_fence = !self.in_fence;
+            return true;
+        }
+        if !self.in_fence && trimmed.starts_with("<code") {
+            self.in_tag = !line.contains("</code>");
+            return true;
+        }
+        self.in_fence
+    }
+}
+
+/// Wraps prose lines longer than `width` at word boundaries, keeping each line's
+/// indentation on its continuation lines. Lines are never joined, and lines of code
+/// (fenced blocks and `<code>` tags) or words longer than `width` are left as they are.
+fn reflow(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut code = CodeSpans::default();
+    for line in text.lines() {
+        if code.is_code(line) || line.chars().count() <= width {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let mut current = String::from(indent);
+        for word in line.split_whitespace() {
+            let fits = current.chars().count() + 1 + word.chars().count() <= width;
+            if current.len() > indent.len() && !fits {
+                out.push_str(&current);
+                out.push('\n');
+                current = String::from(indent);
+            }
+            if current.len() > indent.len() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        out.push_str(&current);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines of `text` outside fenced code blocks and `<code>` tags.
+    fn prose_lines(text: &str) -> Vec<&str> {
+        let mut code = CodeSpans::default();
+        text.lines().filter(|line| !code.is_code(line)).collect()
+    }
+
+    #[test]
+    fn test_reflow_wraps_prose_and_keeps_code() {
+        let text = "Every file must be properly annotated with its relative path, \
+                    which is how the generator knows where to write it.\n\
+                    \n  - an indented list item that is also rather longer than the width\n\
+                    ```rust\nfn main() { println!(\"a code line that is far too long to fit\"); }\n```\n\
+                    <code path=\"Cargo.toml\"> [package] name = \"sample\" </code>\n\
+                    <code path=\"src/lib.rs\">\npub fn a_tagged_line_that_is_too_long() {}\n</code>\n";
+        let wrapped = reflow(text, 30);
+        assert!(
+            prose_lines(&wrapped)
+                .iter()
+                .all(|l| l.chars().count() <= 30)
+        );
+        assert!(wrapped.contains(
+            "\n  - an indented list item that\n  is also rather longer than\n  the width\n"
+        ));
+        assert!(wrapped.contains(
+            "```rust\nfn main() { println!(\"a code line that is far too long to fit\"); }\n```\n"
+        ));
+        assert!(wrapped.contains(
+            "\n<code path=\"Cargo.toml\"> [package] name = \"sample\" </code>\n\
+             <code path=\"src/lib.rs\">\npub fn a_tagged_line_that_is_too_long() {}\n</code>\n"
+        ));
+        assert_eq!(
+            wrapped.split_whitespace().collect::<Vec<_>>(),
+            text.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_prompt_respects_wrap_width() {
+        for width in [40, DEFAULT_WRAP_WIDTH] {
+            let prompt = prompt_markdown(width);
+            assert!(
+                prose_lines(&prompt)
+                    .iter()
+                    .all(|l| l.chars().count() <= width)
+            );
+            assert!(prompt.contains(
+                "\n<code path=\"Cargo.toml\"> [package] name = \"sample_project\" \
+                 version = \"0.1.0\" edition = \"2021\" </code>\n"
+            ));
+        }
+    }
 }
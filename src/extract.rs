@@ -1,14 +1,171 @@
-use std::{fs, path::{Path, PathBuf}};
-use anyhow::{Result, Context};
-use ignore::WalkBuilder;
 use crate::MdPatternCli;
+use crate::cache::{CachedSection, ExtractCache};
+use crate::minify;
+use crate::module_map;
+use anyhow::{Context, Result, bail};
+use ignore::WalkBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use prk_mdgen::parser;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+/// What to do when a file selected for embedding cannot be read.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReadErrorPolicy {
+    /// Abort the whole extraction.
+    #[default]
+    Fail,
+    /// Log a warning and leave a note in place of the file's contents.
+    Skip,
+}
+
+/// What to do with binary files (those containing a NUL byte).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BinaryPolicy {
+    /// List them in the tree only.
+    #[default]
+    Skip,
+    /// Embed their bytes base64-encoded, with a note of the size and MIME type.
+    Base64,
+}
+
+/// Character used for the code fences `extract` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FenceChar {
+    /// ```` ``` ````, which prk_mdgen parses back.
+    #[default]
+    Backtick,
+    /// `~~~`, so the output can sit inside a backtick-fenced block. The generation
+    /// patterns only recognise backtick fences.
+    Tilde,
+}
+
+impl FenceChar {
+    fn char(self) -> char {
+        match self {
+            FenceChar::Backtick => '`',
+            FenceChar::Tilde => '~',
+        }
+    }
+
+    /// A fence of `len` characters.
+    fn fence(self, len: usize) -> String {
+        self.char().to_string().repeat(len)
+    }
+}
+
+/// Shape of the extraction output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExtractFormat {
+    /// One Markdown document with the tree and a section per file.
+    #[default]
+    Markdown,
+    /// One JSON object (`path`, `language`, `content`) per line, per file.
+    Ndjson,
+    /// One AsciiDoc document, each file in a `[source,lang]` listing block.
+    AsciiDoc,
+}
 
+#[derive(Default)]
 pub struct ExtractConfig {
     pub root: PathBuf,
+    /// Extra ignore file (gitignore syntax); its `!` patterns can re-include files
+    /// the git ignores exclude.
     pub ignore_file: Option<PathBuf>,
+    /// `--skip` entries: path prefixes or component names to exclude, or `!path`
+    /// prefixes to include regardless of skips and ignores.
     pub extra_ignores: Vec<String>,
+    /// When non-empty, only files under one of these path prefixes are included.
+    pub only: Vec<String>,
+    /// Comma-separated project kinds ("rust", "node", "flutter") whose directory rules
+    /// are unioned, or "auto" to detect them from the root manifests.
     pub project_type: Option<String>,
     pub pattern: Option<MdPatternCli>,
+    /// Emit each directory's `README.md` as prose ahead of the first file under that
+    /// directory, including files in its subdirectories.
+    pub inline_readmes: bool,
+    /// Files smaller than this many bytes are listed in the tree but not embedded.
+    pub min_file_size: Option<u64>,
+    /// Files larger than this many bytes are listed in the tree but never read.
+    pub max_file_size: Option<u64>,
+    /// Only embed files whose content matches this regex (the tree still lists everything).
+    pub content_filter: Option<Regex>,
+    /// With `content_filter`, embed only the matching lines with this many lines of
+    /// context around each, eliding the rest with `...`.
+    pub context_lines: Option<usize>,
+    /// Only embed files whose extension maps to this fence language (e.g. "rust").
+    pub language_only: Option<String>,
+    /// Renames fence languages before rendering (e.g. "rust" -> "rs"); filters still
+    /// see the original language.
+    pub fence_aliases: BTreeMap<String, String>,
+    /// Annotate each file section with a short SHA-256 of its content.
+    pub hashes: bool,
+    /// `(from, to)` substring replacements applied, in order, to every displayed path
+    /// (tree, headings, anchors and listings); files are still read from their real paths.
+    pub anonymize: Vec<(String, String)>,
+    /// Apply the `anonymize` replacements to embedded content as well.
+    pub anonymize_content: bool,
+    /// Annotate each Rust file section with the modules it declares and the
+    /// `crate::` paths it uses.
+    pub module_map: bool,
+    /// Tag the heading of each file with an executable bit with `[executable]`
+    /// (Unix only; nothing is tagged elsewhere).
+    pub note_exec: bool,
+    /// Render the tree as a nested Markdown list linking each file to its section.
+    pub linked_tree: bool,
+    /// Emit only the tree and a list of paths with sizes, without reading any file.
+    pub manifest_only: bool,
+    /// Emit only the tree and each file's line-ending style (LF, CRLF or mixed),
+    /// followed by a summary, instead of the file contents.
+    pub line_ending_report: bool,
+    /// Whether an unreadable file aborts the extraction or is skipped.
+    pub on_read_error: ReadErrorPolicy,
+    /// Whether binary files are skipped or embedded as base64 (Markdown output only).
+    pub binary: BinaryPolicy,
+    /// Walk everything under `root`: no gitignore, `.aiignore`, git exclude or hidden-file
+    /// filtering (`.git` itself is still skipped). `ignore_file` and `extra_ignores` still apply.
+    pub include_ignored: bool,
+    /// Restrict the walk to files tracked by git (`git ls-files`).
+    pub tracked_only: bool,
+    /// Embed exactly the files listed (one path relative to `root` per line) in the
+    /// listed order, instead of walking `root`; a missing entry is an error.
+    pub from_list: Option<PathBuf>,
+    /// Only include files whose filesystem mtime lies within this long before now.
+    pub modified_within: Option<Duration>,
+    /// Directory for the incremental cache of rendered sections.
+    pub cache_dir: Option<PathBuf>,
+    /// Head each directory with a heading one level deeper than its parent's and
+    /// nest file headings below it, so the document outline mirrors the tree.
+    pub nested_headings: bool,
+    /// Strip insignificant whitespace from Rust and JS/TS sources before embedding.
+    pub minify: bool,
+    /// With `minify`, also drop import statements (`use` / `import`).
+    pub strip_imports: bool,
+    /// Fence language for files whose extension has no mapping (otherwise left blank).
+    pub default_lang: Option<String>,
+    /// Strip trailing whitespace from each embedded line (the files on disk are untouched).
+    pub trim_trailing_ws: bool,
+    /// Heading text for each text file section, with `{path}`, `{lang}`, `{size}`
+    /// (bytes), `{lines}` and `{hash}` substituted; sections then render as
+    /// `### <heading>` plus a fenced block, whatever the pattern.
+    pub header_template: Option<String>,
+    /// Cut embedded lines longer than this many characters, marking them `… [truncated]`.
+    pub max_line_length: Option<usize>,
+    /// Markdown document or NDJSON records.
+    pub format: ExtractFormat,
+    /// Fence character of the Markdown output's code blocks and tree.
+    pub fence_char: FenceChar,
 }
 
 /// Simple project tree generator with no params — uses current dir
@@ -41,156 +198,2839 @@ pub fn generate_tree_markdown() -> Result<String> {
     Ok(md)
 }
 
-/// Walks the directory, applies ignores & skips, builds a tree and dumps every file into Markdown.
-pub fn extract_to_markdown(config: ExtractConfig) -> Result<String> {
-    // 1) Build the walker with .gitignore etc.
+/// Walks the directory, applies ignores & skips, and streams the tree and each file
+/// section straight into `w` instead of building the whole document in memory.
+pub fn extract_to_writer<W: Write>(config: ExtractConfig, w: W) -> Result<()> {
+    extract_with_reader(config, w, &FsReader)
+}
+
+/// Ignore files (gitignore syntax) that exclude files from AI tools' context, honoured
+/// in every directory alongside `.gitignore`.
+const AI_IGNORE_FILES: &[&str] = &[".cursorignore", ".aiignore"];
+
+/// How many leading bytes are inspected to decide whether a file is binary.
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// Reads the contents of source files; abstracted so tests can observe file access.
+pub trait SourceReader: Sync {
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String>;
+
+    /// Reads at most `limit` bytes from the start of the file.
+    fn read_prefix(&self, path: &Path, limit: usize) -> std::io::Result<Vec<u8>> {
+        let mut prefix = Vec::with_capacity(limit);
+        fs::File::open(path)?
+            .take(limit as u64)
+            .read_to_end(&mut prefix)?;
+        Ok(prefix)
+    }
+
+    /// Reads the whole file as bytes, for binaries embedded as base64.
+    fn read_bytes(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+}
+
+/// Reads source files straight from disk.
+pub struct FsReader;
+
+impl SourceReader for FsReader {
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+fn extract_with_reader<W: Write>(
+    config: ExtractConfig,
+    mut w: W,
+    reader: &dyn SourceReader,
+) -> Result<()> {
+    // 1) Walk the tree, applying the ignore files and .gitignore in the walker
+    let filter = FileFilter::new(&config)?;
+    let respect = !config.include_ignored;
     let mut builder = WalkBuilder::new(&config.root);
-    if let Some(ignore) = &config.ignore_file {
-        builder.add_ignore(ignore);
+    // The root .gitignore applies even when `root` is not inside a git repository.
+    let root_gitignore = config.root.join(".gitignore");
+    if respect && root_gitignore.is_file() {
+        builder.add_ignore(&root_gitignore);
+    }
+    // `parents` also applies .gitignore files above `root`, up to the enclosing git repo,
+    // so extracting a subdirectory honours the repository's top-level ignores.
+    builder
+        .git_ignore(respect)
+        .git_exclude(respect)
+        .git_global(respect)
+        .ignore(respect)
+        .parents(respect)
+        .require_git(true)
+        .hidden(false);
+    if respect {
+        for name in AI_IGNORE_FILES {
+            builder.add_custom_ignore_filename(name);
+        }
+    }
+    builder.filter_entry(walk_filter(
+        config.root.clone(),
+        respect,
+        filter.ignore_file.clone(),
+        None,
+    ));
+
+    // 2) Collect all candidate files, with whether the walker's ignore rules dropped them
+    let mut candidates: Vec<(PathBuf, bool)> = Vec::new();
+    if config.from_list.is_none() {
+        let mut walked = HashSet::new();
+        for entry in builder.build() {
+            let entry = entry?;
+            if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                walked.insert(entry.path().to_path_buf());
+                candidates.push((entry.into_path(), false));
+            }
+        }
+        // Files that an explicit include may bring back despite the ignores.
+        if respect && filter.has_overrides() {
+            let mut builder = WalkBuilder::new(&config.root);
+            builder.standard_filters(false).filter_entry(walk_filter(
+                config.root.clone(),
+                true,
+                None,
+                filter.override_prefixes(),
+            ));
+            for entry in builder.build() {
+                let entry = entry?;
+                if entry.file_type().is_some_and(|ft| ft.is_file())
+                    && !walked.contains(entry.path())
+                {
+                    candidates.push((entry.into_path(), true));
+                }
+            }
+        }
     }
-    builder.git_ignore(true).git_exclude(true).hidden(true);
-    let walker = builder.build();
 
-    // 2) Collect all candidate files
+    let tracked = if config.tracked_only {
+        Some(git_tracked_files(&config.root)?)
+    } else {
+        None
+    };
+    let modified_after = config.modified_within.map(|within| {
+        SystemTime::now()
+            .checked_sub(within)
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    });
     let mut files: Vec<PathBuf> = Vec::new();
-    for entry in walker {
-        let entry = entry?;
-        if !entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+    let mut readmes: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for (path, ignored) in candidates {
+        if tracked
+            .as_ref()
+            .is_some_and(|tracked| !tracked.contains(&path))
+        {
             continue;
         }
-        let path = entry.into_path();
-        if !should_include(&path, &config) {
+        if config.inline_readmes
+            && !ignored
+            && path.file_name().is_some_and(|n| n == "README.md")
+            && let Some(dir) = path.parent()
+        {
+            readmes.insert(dir.to_path_buf(), path.clone());
+        }
+        if !filter.should_include_path(&path, ignored) {
+            continue;
+        }
+        if let Some(after) = modified_after
+            && !modified_since(&path, after)
+        {
             continue;
         }
         files.push(path);
     }
+    if let Some(list) = &config.from_list {
+        files = read_file_list(list, &config.root)?;
+    }
 
     // Early exit if no files
+    if files.is_empty() && config.format == ExtractFormat::Ndjson {
+        return Ok(());
+    }
+    if files.is_empty() && config.format == ExtractFormat::AsciiDoc {
+        w.write_all(b"= Project structure\n\n_No files found_\n")?;
+        return Ok(());
+    }
     if files.is_empty() {
-        return Ok("# Project structure\n\n*No files found*\n".to_string());
+        w.write_all(b"# Project structure\n\n*No files found*\n")?;
+        return Ok(());
     }
 
-    // 3) Sort and apply --skip filters
-    files.sort();
-    files.retain(|path| {
-        let rel = path.strip_prefix(&config.root)
-            .map(Path::to_path_buf)
-            .unwrap_or_else(|_| path.clone());
-        let rel_str = rel.to_string_lossy();
-        !config.extra_ignores.iter().any(|pat| {
-            rel_str.starts_with(pat) || rel.components().any(|c| *c.as_os_str() == **pat)
-        })
-    });
+    // 3) Sort; a file list keeps its own order
+    if config.from_list.is_none() {
+        files.sort();
+    }
+
+    if config.format == ExtractFormat::Ndjson {
+        return write_ndjson(&files, &config, &filter, reader, w);
+    }
+    if config.format == ExtractFormat::AsciiDoc {
+        return write_asciidoc(&files, &config, &filter, reader, w);
+    }
 
-    // 4) Build an ASCII tree
-    let tree = build_tree(&files, &config.root);
+    // Anchors are assigned up front so the linked tree can point at the sections.
+    let mut anchors = Anchors::default();
+    let anchor_ids: HashMap<PathBuf, String> = files
+        .iter()
+        .map(|path| (path.clone(), anchors.anchor(&display_path(path, &config))))
+        .collect();
 
-    // 5) Emit Markdown
-    let mut md = String::new();
-    md.push_str("# Project structure\n\n");
-    md.push_str("```\n");
-    md.push_str(&tree);
-    md.push_str("```\n\n");
+    let embeds_sections = !config.line_ending_report && !config.manifest_only;
+    let cache = match &config.cache_dir {
+        Some(dir) if embeds_sections => Some(ExtractCache::open(dir, render_fingerprint(&config))?),
+        _ => None,
+    };
+    let load_batch = |batch: &[PathBuf]| -> Result<Vec<LoadedFile>> {
+        let loaded = batch
+            .par_iter()
+            .map(|path| load_file(path, &config, &filter, reader, cache.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(loaded.into_iter().flatten().collect())
+    };
+    // The linked tree may only link sections that are emitted, and the filters that
+    // drop a file need its content, so every section is loaded before the tree.
+    let preloaded = if config.linked_tree && embeds_sections {
+        Some(load_batch(&files)?)
+    } else {
+        None
+    };
+
+    // 4) Build the tree, as ASCII art or as a linked list
+    let shown = displayed_paths(&files, &config);
+    if config.linked_tree {
+        let emitted: HashSet<&PathBuf> = preloaded.iter().flatten().map(|f| &f.path).collect();
+        let shown_anchors = shown
+            .iter()
+            .zip(&files)
+            .filter(|(_, path)| emitted.contains(path))
+            .map(|(shown, path)| (shown.clone(), anchor_ids[path].clone()))
+            .collect();
+        let tree = build_linked_tree(&shown, &config.root, &shown_anchors);
+        write!(w, "# Project structure\n\n{}\n", tree)?;
+    } else {
+        let tree = build_tree(&shown, &config.root);
+        let fence = config.fence_char.fence(3);
+        write!(w, "# Project structure\n\n{0}\n{1}{0}\n\n", fence, tree)?;
+    }
 
+    if config.line_ending_report {
+        return write_line_ending_report(&files, &config, reader, w);
+    }
+
+    if config.manifest_only {
+        writeln!(w, "# Files\n")?;
+        for path in &files {
+            let size = fs::metadata(path)
+                .with_context(|| format!("failed to read metadata: {:?}", path))?
+                .len();
+            writeln!(w, "- `{}` ({} bytes)", display_path(path, &config), size)?;
+        }
+        w.flush()?;
+        return Ok(());
+    }
+
+    let mut introduced_dirs: HashSet<PathBuf> = HashSet::new();
+    let mut headed_dirs: HashSet<PathBuf> = HashSet::new();
+    if config.nested_headings {
+        writeln!(w, "# Files\n")?;
+    }
+    // Read files in parallel a batch at a time, then render each batch in order, so
+    // output stays deterministic and (without a linked tree) only one batch of
+    // contents is held in memory.
+    let batches: Box<dyn Iterator<Item = Result<Vec<LoadedFile>>>> = match preloaded {
+        Some(loaded) => Box::new(std::iter::once(Ok(loaded))),
+        None => Box::new(files.chunks(READ_BATCH).map(load_batch)),
+    };
+    for loaded in batches {
+        for file in loaded? {
+            let rel = PathBuf::from(display_path(&file.path, &config));
+            let depth = rel.components().count().saturating_sub(1);
+            if config.nested_headings {
+                w.write_all(directory_headings(&rel, &mut headed_dirs).as_bytes())?;
+            }
+
+            // lead the first file under each directory with that directory's README
+            // prose, outermost directory first
+            let mut dirs: Vec<&Path> = file
+                .path
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(&config.root))
+                .collect();
+            dirs.reverse();
+            for dir in dirs {
+                if introduced_dirs.insert(dir.to_path_buf())
+                    && let Some(readme) = readmes.get(dir)
+                {
+                    let prose = fs::read_to_string(readme)
+                        .with_context(|| format!("failed to read file: {:?}", readme))?;
+                    write!(w, "{}\n\n", prose.trim())?;
+                }
+            }
+
+            // anchor the section so it can be linked to, then build the block
+            write!(w, "<a id=\"{}\"></a>", anchor_ids[&file.path])?;
+            if let Some(hash) = &file.hash {
+                write!(w, " <!-- sha256: {} -->", hash)?;
+            }
+            if let Some(modules) = &file.modules {
+                write!(w, " <!-- {} -->", modules)?;
+            }
+            writeln!(w)?;
+            let block = if file.executable {
+                mark_executable(&file.block, &rel.to_string_lossy())
+            } else {
+                file.block
+            };
+            if config.nested_headings {
+                w.write_all(nest_file_heading(&block, depth).as_bytes())?;
+            } else {
+                w.write_all(block.as_bytes())?;
+            }
+        }
+    }
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
+
+/// Line-ending style of a text file, for `--line-ending-report`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EolStyle {
+    Lf,
+    Crlf,
+    Mixed,
+    /// No line breaks at all.
+    None,
+}
+
+impl EolStyle {
+    fn detect(content: &str) -> Self {
+        let crlf = content.matches("\r\n").count();
+        let lf = content.matches('\n').count() - crlf;
+        match (lf, crlf) {
+            (0, 0) => EolStyle::None,
+            (_, 0) => EolStyle::Lf,
+            (0, _) => EolStyle::Crlf,
+            _ => EolStyle::Mixed,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            EolStyle::Lf => "LF",
+            EolStyle::Crlf => "CRLF",
+            EolStyle::Mixed => "mixed",
+            EolStyle::None => "no line breaks",
+        }
+    }
+}
+
+/// Lists each file's [`EolStyle`] (binaries are noted, not inspected), then a tally.
+fn write_line_ending_report<W: Write>(
+    files: &[PathBuf],
+    config: &ExtractConfig,
+    reader: &dyn SourceReader,
+    mut w: W,
+) -> Result<()> {
+    writeln!(w, "# Line endings\n")?;
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
     for path in files {
-        // compute relative path, normalize separators
-        let rel = path.strip_prefix(&config.root)
-            .map(Path::to_path_buf)
-            .unwrap_or_else(|_| path.clone());
-        let rel_raw = rel.to_string_lossy().to_string();
-        let rel_str = rel_raw.replace('\\', "/");
-        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        let lang = match ext {
-            "rs" => "rust",
-            "toml" => "toml",
-            "json" => "json",
-            "dart" => "dart",
-            "js" => "javascript",
-            "ts" => "typescript",
-            _ => "",
-        };
-        let content = fs::read_to_string(&path)
+        let content = read_source(path, reader)
             .with_context(|| format!("failed to read file: {:?}", path))?;
+        let label = content.map_or("binary", |content| EolStyle::detect(&content).label());
+        *counts.entry(label).or_default() += 1;
+        writeln!(w, "- `{}`: {}", display_path(path, config), label)?;
+    }
+    let summary: Vec<String> = ["LF", "CRLF", "mixed", "no line breaks", "binary"]
+        .iter()
+        .filter_map(|label| counts.get(label).map(|n| format!("{} {}", n, label)))
+        .collect();
+    writeln!(w, "\n**Summary:** {}", summary.join(", "))?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Whether the file was last modified at or after `after`; files whose mtime
+/// cannot be read are kept.
+fn modified_since(path: &Path, after: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .map_or(true, |mtime| mtime >= after)
+}
+
+/// Reads the paths listed in `list` (blank lines and `#` comments ignored), resolved
+/// against `root`, failing on the first one that is absolute, leads out of `root`
+/// or is not a file.
+fn read_file_list(list: &Path, root: &Path) -> Result<Vec<PathBuf>> {
+    let text = fs::read_to_string(list)
+        .with_context(|| format!("failed to read file list: {:?}", list))?;
+    let mut files = Vec::new();
+    for entry in text.lines().map(str::trim) {
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        if !stays_under_root(Path::new(entry)) {
+            bail!(
+                "{} is listed in {:?} but is not a path under the root",
+                entry,
+                list
+            );
+        }
+        let path = root.join(entry);
+        if !path.is_file() {
+            bail!("{} is listed in {:?} but does not exist", entry, list);
+        }
+        files.push(path);
+    }
+    Ok(files)
+}
+
+/// Whether the relative path `rel`, with its `.` and `..` components resolved,
+/// stays inside the directory it is relative to.
+fn stays_under_root(rel: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in rel.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(up) => depth = up,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Headings for the directories above `rel` that have not been headed yet,
+/// `##` for a top-level directory and one more `#` per level (capped at six).
+fn directory_headings(rel: &Path, headed: &mut HashSet<PathBuf>) -> String {
+    let mut out = String::new();
+    let Some(parent) = rel.parent() else {
+        return out;
+    };
+    let mut dir = PathBuf::new();
+    for (depth, component) in parent.components().enumerate() {
+        dir.push(component);
+        if headed.insert(dir.clone()) {
+            let level = "#".repeat((depth + 2).min(6));
+            out.push_str(&format!(
+                "{} {}/\n\n",
+                level,
+                rel_string(&dir, Path::new(""))
+            ));
+        }
+    }
+    out
+}
+
+/// Moves a block's `###` file heading one level below its directory's heading;
+/// files at the root keep `###`. Blocks without a leading heading are unchanged.
+fn nest_file_heading(block: &str, depth: usize) -> String {
+    match block.strip_prefix("### ") {
+        Some(rest) => format!("{} {}", "#".repeat((depth + 2).clamp(3, 6)), rest),
+        None => block.to_string(),
+    }
+}
+
+/// Number of files read concurrently before their sections are rendered.
+const READ_BATCH: usize = 256;
+
+/// A file selected for embedding, with its section already rendered.
+struct LoadedFile {
+    path: PathBuf,
+    hash: Option<String>,
+    modules: Option<String>,
+    /// Whether to tag the section `[executable]` (`--note-exec`); checked on every
+    /// run since a mode change does not invalidate the cache.
+    executable: bool,
+    block: String,
+}
+
+/// Reads `path` and applies the per-file filters; `None` means the file is not embedded.
+///
+/// With a cache, unchanged files are served from it without being read or rendered.
+fn load_file(
+    path: &Path,
+    config: &ExtractConfig,
+    filter: &FileFilter,
+    reader: &dyn SourceReader,
+    cache: Option<&ExtractCache>,
+) -> Result<Option<LoadedFile>> {
+    let meta =
+        fs::metadata(path).with_context(|| format!("failed to read metadata: {:?}", path))?;
+    if outside_size_limits(&meta, config) {
+        return Ok(None);
+    }
+
+    let rel_str = display_path(path, config);
+    let lang = lang_for_path(path);
+    if !filter.accepts_language(lang) {
+        return Ok(None);
+    }
+
+    // Cache entries are keyed by the real path, which stays unique under `--anonymize`.
+    let cache_key = rel_string(path, &config.root);
+    let section = match cache.and_then(|c| c.get(&cache_key, &meta)) {
+        Some(section) => section,
+        None => {
+            let content = match read_source(path, reader) {
+                Ok(content) => content,
+                Err(err) if config.on_read_error == ReadErrorPolicy::Skip => {
+                    log::warn!("Skipping unreadable file {}: {}", rel_str, err);
+                    return Ok(Some(LoadedFile {
+                        path: path.to_path_buf(),
+                        block: format!("*Skipped `{}`: could not read it ({}).*\n\n", rel_str, err),
+                        hash: None,
+                        modules: None,
+                        executable: false,
+                    }));
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("failed to read file: {:?}", path));
+                }
+            };
+            let section = match content {
+                Some(content) => render_section(config, &rel_str, lang, &content),
+                None if config.binary == BinaryPolicy::Base64
+                    && config.content_filter.is_none() =>
+                {
+                    let bytes = reader
+                        .read_bytes(path)
+                        .with_context(|| format!("failed to read file: {:?}", path))?;
+                    render_binary_section(config, &rel_str, &bytes)
+                }
+                None => CachedSection {
+                    hash: None,
+                    modules: None,
+                    block: None,
+                },
+            };
+            if let Some(cache) = cache {
+                cache.put(&cache_key, &meta, &section)?;
+            }
+            section
+        }
+    };
+
+    Ok(section.block.map(|block| LoadedFile {
+        path: path.to_path_buf(),
+        hash: section.hash,
+        modules: section.modules,
+        executable: config.note_exec && is_executable(&meta),
+        block,
+    }))
+}
+
+/// Whether any of the file's executable permission bits is set.
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+/// Permission bits are not tracked outside Unix, so nothing is executable.
+#[cfg(not(unix))]
+fn is_executable(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+/// Appends ` [executable]` to the line of `block` naming `rel_str` (the first line
+/// when a header template left the path out).
+fn mark_executable(block: &str, rel_str: &str) -> String {
+    let line_end = match block.lines().position(|line| line.contains(rel_str)) {
+        Some(index) => block
+            .split_inclusive('\n')
+            .take(index + 1)
+            .map(str::len)
+            .sum::<usize>(),
+        None => block.find('\n').map_or(block.len(), |i| i + 1),
+    };
+    let end = block[..line_end].trim_end_matches(['\r', '\n']).len();
+    format!("{} [executable]{}", &block[..end], &block[end..])
+}
+
+/// Whether the file is smaller than `--min-file-size` or larger than `--max-file-size`.
+fn outside_size_limits(meta: &fs::Metadata, config: &ExtractConfig) -> bool {
+    config.min_file_size.is_some_and(|min| meta.len() < min)
+        || config.max_file_size.is_some_and(|max| meta.len() > max)
+}
+
+/// One line of the NDJSON output.
+#[derive(Serialize)]
+struct FileRecord {
+    path: String,
+    language: &'static str,
+    content: String,
+}
+
+/// Streams one JSON object per embeddable file, applying the same filters as the
+/// Markdown output, in batches like [`extract_with_reader`].
+fn write_ndjson<W: Write>(
+    files: &[PathBuf],
+    config: &ExtractConfig,
+    filter: &FileFilter,
+    reader: &dyn SourceReader,
+    mut w: W,
+) -> Result<()> {
+    for batch in files.chunks(READ_BATCH) {
+        let records = batch
+            .par_iter()
+            .map(|path| load_record(path, config, filter, reader))
+            .collect::<Result<Vec<_>>>()?;
+        for record in records.into_iter().flatten() {
+            serde_json::to_writer(&mut w, &record)?;
+            w.write_all(b"\n")?;
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes the tree and one `[source,lang]` listing per embeddable file as AsciiDoc,
+/// selecting and reading files like [`write_ndjson`].
+fn write_asciidoc<W: Write>(
+    files: &[PathBuf],
+    config: &ExtractConfig,
+    filter: &FileFilter,
+    reader: &dyn SourceReader,
+    mut w: W,
+) -> Result<()> {
+    let tree = build_tree(&displayed_paths(files, config), &config.root);
+    write!(w, "= Project structure\n\n....\n{}....\n\n", tree)?;
+    for batch in files.chunks(READ_BATCH) {
+        let records = batch
+            .par_iter()
+            .map(|path| load_record(path, config, filter, reader))
+            .collect::<Result<Vec<_>>>()?;
+        for record in records.into_iter().flatten() {
+            let lang = match &config.default_lang {
+                Some(default) if record.language.is_empty() => default.as_str(),
+                _ => record.language,
+            };
+            let lang = config.fence_aliases.get(lang).map_or(lang, String::as_str);
+            let source = if lang.is_empty() {
+                "[source]".to_string()
+            } else {
+                format!("[source,{}]", lang)
+            };
+            // A listing closes at a line of exactly its delimiter, so outgrow any in the file.
+            let delimiter = "-".repeat(longest_dash_line(&record.content).max(3) + 1);
+            write!(
+                w,
+                "== {}\n\n{}\n{2}\n{3}\n{2}\n\n",
+                record.path,
+                source,
+                delimiter,
+                record.content.trim_end()
+            )?;
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Length of the longest line of `content` made only of `-` characters.
+fn longest_dash_line(content: &str) -> usize {
+    content
+        .lines()
+        .filter(|line| !line.is_empty() && line.bytes().all(|b| b == b'-'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Reads `path` into a record, or `None` when a filter excludes it or it is binary
+/// (or unreadable under the skip policy).
+fn load_record(
+    path: &Path,
+    config: &ExtractConfig,
+    filter: &FileFilter,
+    reader: &dyn SourceReader,
+) -> Result<Option<FileRecord>> {
+    let meta =
+        fs::metadata(path).with_context(|| format!("failed to read metadata: {:?}", path))?;
+    let language = lang_for_path(path);
+    if outside_size_limits(&meta, config) || !filter.accepts_language(language) {
+        return Ok(None);
+    }
+    let rel_str = display_path(path, config);
+    let content = match read_source(path, reader) {
+        Ok(Some(content)) => content,
+        Ok(None) => return Ok(None),
+        Err(err) if config.on_read_error == ReadErrorPolicy::Skip => {
+            log::warn!("Skipping unreadable file {}: {}", rel_str, err);
+            return Ok(None);
+        }
+        Err(err) => return Err(err).with_context(|| format!("failed to read file: {:?}", path)),
+    };
+    if config
+        .content_filter
+        .as_ref()
+        .is_some_and(|filter| !filter.is_match(&content))
+    {
+        return Ok(None);
+    }
+    let content = embedded_content(config, language, &content).into_owned();
+    Ok(Some(FileRecord {
+        path: rel_str,
+        language,
+        content,
+    }))
+}
+
+/// Reads a source file, sniffing its start first so binaries (`None`) are never fully read.
+fn read_source(path: &Path, reader: &dyn SourceReader) -> std::io::Result<Option<String>> {
+    let prefix = reader.read_prefix(path, SNIFF_LEN)?;
+    if looks_binary(&prefix) {
+        return Ok(None);
+    }
+    reader.read_to_string(path).map(Some)
+}
+
+/// Treats a file as binary when its leading bytes contain a NUL, as git does.
+fn looks_binary(prefix: &[u8]) -> bool {
+    prefix.contains(&0)
+}
 
-        // build the block
-        let block = match config.pattern {
-            Some(MdPatternCli::CodeTag) => format!(
-                "<code path=\"{0}\">\n{1}\n</code>\n\n", 
-                rel_str, content.trim()
-            ),
-            Some(MdPatternCli::Hash) => format!(
-                "### {0}\n{1}", rel_str, fenced(&rel_str, lang, &content)
-            ),
-            Some(MdPatternCli::Delimiter) => format!(
-                "========\n{0}\n========\n{1}", rel_str, fenced(&rel_str, lang, &content)
-            ),
-            Some(MdPatternCli::Raw) => format!(
-                "// file: {0}\n{1}", rel_str, fenced(&rel_str, lang, &content)
-            ),
-            Some(MdPatternCli::FileCode) => format!(
-                "<file> {0} </file>\n<code>\n{1}\n</code>\n\n", 
-                rel_str, content.trim()
-            ),
-            Some(MdPatternCli::FileFence) | None => format!(
-                "### <file> {0} </file>\n{1}", rel_str, fenced(&rel_str, lang, &content)
-            ),
+/// Applies the content-based filters and renders the file's block.
+///
+/// Filters and hashes see the file as it is on disk; minification and fence
+/// aliases only affect the rendered block.
+fn render_section(
+    config: &ExtractConfig,
+    rel_str: &str,
+    lang: &str,
+    content: &str,
+) -> CachedSection {
+    if let Some(filter) = &config.content_filter
+        && !filter.is_match(content)
+    {
+        return CachedSection {
+            hash: None,
+            modules: None,
+            block: None,
         };
-        md.push_str(&block);
     }
+    let body = embedded_content(config, lang, content);
+    let lang = match &config.default_lang {
+        Some(default) if lang.is_empty() => default.as_str(),
+        _ => lang,
+    };
+    let fence_lang = config.fence_aliases.get(lang).map_or(lang, String::as_str);
+    let block = match &config.header_template {
+        Some(template) => format!(
+            "### {}\n{}",
+            expand_header(template, rel_str, fence_lang, content),
+            fenced(config.fence_char, rel_str, fence_lang, &body)
+        ),
+        None => render_block(
+            config.pattern,
+            config.fence_char,
+            rel_str,
+            fence_lang,
+            &body,
+        ),
+    };
+    let modules = (config.module_map && lang == "rust")
+        .then(|| module_map::scan(content))
+        .filter(|map| !map.is_empty())
+        .map(|map| map.annotation());
+    CachedSection {
+        hash: config.hashes.then(|| short_hash(content.as_bytes())),
+        modules,
+        block: Some(block),
+    }
+}
 
-    Ok(md)
+/// Substitutes the `--header-template` placeholders for one file.
+fn expand_header(template: &str, rel_str: &str, lang: &str, content: &str) -> String {
+    template
+        .replace("{path}", rel_str)
+        .replace("{lang}", lang)
+        .replace("{size}", &content.len().to_string())
+        .replace("{lines}", &content.lines().count().to_string())
+        .replace("{hash}", &short_hash(content.as_bytes()))
 }
 
-/// Helper to produce a fenced code block with language and content
-fn fenced(rel: &str, lang: &str, content: &str) -> String {
-    format!("```{}\n{}\n```\n\n", lang, content.trim())
+/// Renders a binary file as a `base64` block followed by its size and MIME type.
+fn render_binary_section(config: &ExtractConfig, rel_str: &str, bytes: &[u8]) -> CachedSection {
+    let block = render_block(
+        config.pattern,
+        config.fence_char,
+        rel_str,
+        "base64",
+        &base64_encode(bytes),
+    );
+    let note = format!(
+        "*Binary file: {} bytes, {}, embedded as base64.*\n\n",
+        bytes.len(),
+        mime_guess(rel_str)
+    );
+    CachedSection {
+        hash: config.hashes.then(|| short_hash(bytes)),
+        modules: None,
+        block: Some(block + &note),
+    }
 }
 
-/// Decide inclusion by project_type hint (optional) or by extension.
-fn should_include(path: &Path, config: &ExtractConfig) -> bool {
-    let rel = path.strip_prefix(&config.root).unwrap_or(path);
-    let s = rel.to_string_lossy();
+/// Standard base64 (RFC 4648, padded), wrapped at 76 characters per line.
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4 + bytes.len() / 57 + 1);
+    for (i, chunk) in bytes.chunks(3).enumerate() {
+        if i > 0 && i % 19 == 0 {
+            out.push('\n');
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (j, &b)| n | (b as u32) << (16 - 8 * j));
+        for j in 0..4 {
+            if j <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * j) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out.push('\n');
+    out
+}
 
-    if s == "Cargo.toml" || s == "pubspec.yaml" || s == "package.json" {
-        return true;
+/// MIME type guessed from the file extension.
+fn mime_guess(path: &str) -> &'static str {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match ext.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("wasm") => "application/wasm",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        _ => "application/octet-stream",
     }
+}
 
-    if let Some(pt) = &config.project_type {
-        match pt.as_str() {
-            "flutter" => return s == "pubspec.yaml" || s.starts_with("lib/"),
-            "rust" => return s == "Cargo.toml" || s.starts_with("src/"),
-            "node" => return s == "package.json" || s.starts_with("src/"),
-            _ => {}
+/// The text embedded for a file: its content reduced to the `--context-lines`
+/// regions, then after minification, trailing whitespace trimming and long-line
+/// truncation, when enabled.
+fn embedded_content<'a>(config: &ExtractConfig, lang: &str, content: &'a str) -> Cow<'a, str> {
+    let mut body = Cow::Borrowed(content);
+    // Regions are picked from the real content so the filter sees what is on disk.
+    if let (Some(filter), Some(context)) = (&config.content_filter, config.context_lines)
+        && let Some(regions) = matching_regions(content, filter, context)
+    {
+        body = Cow::Owned(regions);
+    }
+    if config.anonymize_content && !config.anonymize.is_empty() {
+        body = Cow::Owned(anonymize(&body, &config.anonymize));
+    }
+    if config.minify {
+        body = Cow::Owned(minify::minify(lang, &body, config.strip_imports));
+    }
+    if config.trim_trailing_ws {
+        body = Cow::Owned(
+            body.split_inclusive('\n')
+                .map(|line| {
+                    let text = line.trim_end_matches(['\n', '\r']);
+                    format!("{}{}", text.trim_end(), &line[text.len()..])
+                })
+                .collect(),
+        );
+    }
+    if let Some(max) = config.max_line_length
+        && body.lines().any(|line| line.chars().count() > max)
+    {
+        body = Cow::Owned(
+            body.split_inclusive('\n')
+                .map(|line| {
+                    let text = line.trim_end_matches(['\n', '\r']);
+                    match text.char_indices().nth(max) {
+                        Some((cut, _)) => {
+                            format!("{}… [truncated]{}", &text[..cut], &line[text.len()..])
+                        }
+                        None => line.to_string(),
+                    }
+                })
+                .collect(),
+        );
+    }
+    body
+}
+
+/// The lines of `content` matching `filter`, each with `context` lines around it
+/// (grep -C style), with `...` between non-adjacent regions. `None` when no single
+/// line matches, e.g. for a pattern spanning lines.
+fn matching_regions(content: &str, filter: &Regex, context: usize) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut keep = vec![false; lines.len()];
+    let mut matched = false;
+    for (i, line) in lines.iter().enumerate() {
+        if filter.is_match(line) {
+            matched = true;
+            let end = (i + context).min(lines.len() - 1);
+            keep[i.saturating_sub(context)..=end].fill(true);
+        }
+    }
+    if !matched {
+        return None;
+    }
+    let mut out = String::new();
+    let mut last: Option<usize> = None;
+    for (i, line) in lines.iter().enumerate().filter(|(i, _)| keep[*i]) {
+        if last.is_some_and(|last| i > last + 1) {
+            out.push_str("...\n");
         }
+        out.push_str(line);
+        out.push('\n');
+        last = Some(i);
     }
+    Some(out)
+}
 
-    matches!(
-        path.extension().and_then(|e| e.to_str()),
-        Some("rs" | "toml" | "json" | "js" | "ts" | "dart")
+/// Summarises every setting that affects a rendered section, so cached sections
+/// produced under different settings are not reused.
+fn render_fingerprint(config: &ExtractConfig) -> String {
+    format!(
+        "{:?}|{:?}|{:?}|{}|{}|{:?}|{}|{:?}|{}|{}|{}|{:?}|{:?}|{:?}|{:?}|{:?}",
+        config.pattern,
+        config.fence_char,
+        config.content_filter.as_ref().map(Regex::as_str),
+        config.hashes,
+        config.module_map,
+        config.anonymize,
+        config.anonymize_content,
+        config.fence_aliases,
+        config.minify,
+        config.strip_imports,
+        config.trim_trailing_ws,
+        config.default_lang,
+        config.binary,
+        config.max_line_length,
+        config.header_template,
+        config.context_lines,
     )
 }
 
-/// Build a simple ASCII tree representation from a sorted list of file paths.
-fn build_tree(files: &[PathBuf], root: &Path) -> String {
-    let mut tree = String::new();
-    let mut last_parts: Vec<String> = Vec::new();
+/// Renders one file section in the syntax of the requested pattern.
+fn render_block(
+    pattern: Option<MdPatternCli>,
+    fence: FenceChar,
+    rel_str: &str,
+    lang: &str,
+    content: &str,
+) -> String {
+    match pattern {
+        Some(MdPatternCli::CodeTag) => format!(
+            "<code path=\"{0}\">\n{1}\n</code>\n\n",
+            rel_str,
+            content.trim()
+        ),
+        Some(MdPatternCli::Hash) => {
+            format!(
+                "### {0}\n{1}",
+                rel_str,
+                fenced(fence, rel_str, lang, content)
+            )
+        }
+        Some(MdPatternCli::Delimiter) => format!(
+            "========\n{0}\n========\n{1}",
+            rel_str,
+            fenced(fence, rel_str, lang, content)
+        ),
+        Some(MdPatternCli::Raw) => {
+            format!(
+                "// file: {0}\n{1}",
+                rel_str,
+                fenced(fence, rel_str, lang, content)
+            )
+        }
+        Some(MdPatternCli::FileCode) => format!(
+            "<file> {0} </file>\n<code>\n{1}\n</code>\n\n",
+            rel_str,
+            content.trim()
+        ),
+        Some(MdPatternCli::FencedInfoPath) => {
+            let run = longest_run(content, fence.char());
+            format!(
+                "{0}{1} path={2}\n{3}\n{0}\n\n",
+                fence.fence(run.max(2) + 1),
+                lang,
+                rel_str,
+                content.trim()
+            )
+        }
+        Some(MdPatternCli::FileFence) | None => format!(
+            "### <file> {0} </file>\n{1}",
+            rel_str,
+            fenced(fence, rel_str, lang, content)
+        ),
+    }
+}
 
-    for path in files {
-        let rel = path.strip_prefix(root).unwrap_or(path);
-        let parts: Vec<String> = rel.iter().map(|p| p.to_string_lossy().into()).collect();
-        let common = last_parts.iter().zip(&parts).take_while(|(a, b)| a == b).count();
+/// Re-emits every file block of `content`, parsed with the `from` pattern (all
+/// patterns when `None`), in the syntax of the `to` pattern. A block's
+/// `<!-- lang: ... -->` directive overrides the fence language of its extension.
+pub fn convert_markdown(
+    content: &str,
+    from: Option<MdPatternCli>,
+    to: Option<MdPatternCli>,
+) -> String {
+    parser::parse_content(content, from.map(Into::into))
+        .iter()
+        .map(|file| {
+            let lang = file
+                .language
+                .as_deref()
+                .unwrap_or_else(|| lang_for_path(Path::new(&file.path)));
+            render_block(to, FenceChar::Backtick, &file.path, lang, &file.content)
+        })
+        .collect()
+}
+
+/// Hands out stable, unique anchor slugs for file sections.
+///
+/// A slug is derived from the full relative path; if two paths reduce to the
+/// same slug, later ones get `-1`, `-2`, ... appended in emission order.
+#[derive(Default)]
+struct Anchors {
+    seen: HashMap<String, usize>,
+}
+
+impl Anchors {
+    fn anchor(&mut self, rel_path: &str) -> String {
+        let base = slugify(rel_path);
+        let count = self.seen.entry(base.clone()).or_insert(0);
+        let anchor = if *count == 0 {
+            base
+        } else {
+            format!("{}-{}", base, count)
+        };
+        *count += 1;
+        anchor
+    }
+}
+
+/// Lowercases `text` and joins its alphanumeric runs with `-`.
+fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// First 8 hex characters of the SHA-256 of `content`.
+fn short_hash(content: &[u8]) -> String {
+    let digest = Sha256::digest(content);
+    digest[..4].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Length of the longest run of consecutive `ch` characters in `content`.
+fn longest_run(content: &str, ch: char) -> usize {
+    content
+        .split(|c| c != ch)
+        .map(|run| run.len() / ch.len_utf8())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Fence language for `path`, from its name for extensionless files such as
+/// `Makefile`, otherwise from its extension.
+fn lang_for_path(path: &Path) -> &'static str {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    parser::known_basename_lang(name).unwrap_or_else(|| {
+        lang_for_extension(path.extension().and_then(|e| e.to_str()).unwrap_or(""))
+    })
+}
+
+/// Maps a file extension (in any case) to the fence language used when embedding it.
+fn lang_for_extension(ext: &str) -> &'static str {
+    match ext.to_ascii_lowercase().as_str() {
+        "rs" => "rust",
+        "toml" => "toml",
+        "json" => "json",
+        "dart" => "dart",
+        "js" => "javascript",
+        "ts" => "typescript",
+        _ => "",
+    }
+}
+
+/// Helper to produce a fenced code block with language and content
+fn fenced(fence: FenceChar, _rel: &str, lang: &str, content: &str) -> String {
+    format!("{0}{1}\n{2}\n{0}\n\n", fence.fence(3), lang, content.trim())
+}
+
+/// Root-level configuration files that belong to each project type.
+const RUST_ROOT_FILES: &[&str] = &[
+    "Cargo.toml",
+    "build.rs",
+    "rust-toolchain.toml",
+    "rustfmt.toml",
+    ".rustfmt.toml",
+    "clippy.toml",
+];
+const NODE_ROOT_FILES: &[&str] = &[
+    "package.json",
+    "tsconfig.json",
+    "jsconfig.json",
+    ".npmrc",
+    ".nvmrc",
+    ".eslintrc.json",
+    ".prettierrc",
+];
+const FLUTTER_ROOT_FILES: &[&str] = &[
+    "pubspec.yaml",
+    "analysis_options.yaml",
+    "l10n.yaml",
+    "build.yaml",
+];
+
+/// Entry filter for a walk: `.git` is always pruned; with `skip_hidden`, hidden
+/// entries (see [`is_walkable`]) are too. Directories the ignore file excludes are pruned, as
+/// gitignore rules cannot re-include files below them, and with `prefixes` only
+/// directories on the way to or under one of them are entered.
+fn walk_filter(
+    root: PathBuf,
+    skip_hidden: bool,
+    ignore_file: Option<Gitignore>,
+    prefixes: Option<Vec<PathBuf>>,
+) -> impl Fn(&ignore::DirEntry) -> bool + Send + Sync + 'static {
+    move |entry| {
+        if entry.file_name() == ".git" || (skip_hidden && !is_walkable(entry)) {
+            return false;
+        }
+        if entry.depth() == 0 || !entry.file_type().is_some_and(|ft| ft.is_dir()) {
+            return true;
+        }
+        if ignore_file
+            .as_ref()
+            .is_some_and(|gi| gi.matched(entry.path(), true).is_ignore())
+        {
+            return false;
+        }
+        prefixes.as_ref().is_none_or(|prefixes| {
+            let rel = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            prefixes
+                .iter()
+                .any(|prefix| prefix.starts_with(rel) || rel.starts_with(prefix))
+        })
+    }
+}
+
+/// Hidden entries are skipped during the walk, except the root config dotfiles above.
+fn is_walkable(entry: &ignore::DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    entry.depth() == 0
+        || !name.starts_with('.')
+        || (entry.depth() == 1
+            && [RUST_ROOT_FILES, NODE_ROOT_FILES, FLUTTER_ROOT_FILES]
+                .iter()
+                .any(|files| files.contains(&&*name)))
+}
+
+/// Lists the files git tracks under `root`, as absolute paths joined onto `root`.
+fn git_tracked_files(root: &Path) -> Result<HashSet<PathBuf>> {
+    let output = Command::new("git")
+        .args(["ls-files", "-z"])
+        .current_dir(root)
+        .output()
+        .context("failed to run `git ls-files`; is git installed?")?;
+    if !output.status.success() {
+        bail!(
+            "--tracked-only requires a git repository: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output
+        .stdout
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| root.join(String::from_utf8_lossy(p).as_ref()))
+        .collect())
+}
+
+/// Project types with their own set of embedded files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProjectType {
+    Rust,
+    Node,
+    Flutter,
+}
+
+impl ProjectType {
+    const ALL: [Self; 3] = [Self::Rust, Self::Node, Self::Flutter];
+
+    /// Resolves one project kind; unknown names are ignored.
+    fn from_hint(hint: &str) -> Option<Self> {
+        match hint {
+            "rust" => Some(Self::Rust),
+            "node" => Some(Self::Node),
+            "flutter" => Some(Self::Flutter),
+            _ => None,
+        }
+    }
 
-        last_parts.truncate(common);
-        for part in &parts[common..] {
-            for _ in 0..last_parts.len() {
-                tree.push_str("    ");
+    /// Resolves a `--project-type` value into a set of kinds: a comma-separated list,
+    /// or "auto" for every kind whose manifest sits at `root`. An empty set means
+    /// extension matching is used instead.
+    fn resolve(hint: &str, root: &Path) -> Vec<Self> {
+        let mut kinds = Vec::new();
+        for part in hint.split(',').map(str::trim) {
+            let found: Vec<Self> = if part == "auto" {
+                Self::ALL
+                    .into_iter()
+                    .filter(|kind| root.join(kind.manifest()).is_file())
+                    .collect()
+            } else {
+                Self::from_hint(part).into_iter().collect()
+            };
+            for kind in found {
+                if !kinds.contains(&kind) {
+                    kinds.push(kind);
+                }
             }
-            tree.push_str("├── ");
-            tree.push_str(part);
-            tree.push('\n');
-            last_parts.push(part.clone());
         }
+        kinds
     }
 
-    tree
+    fn manifest(self) -> &'static str {
+        match self {
+            Self::Rust => "Cargo.toml",
+            Self::Node => "package.json",
+            Self::Flutter => "pubspec.yaml",
+        }
+    }
+
+    fn root_files(self) -> &'static [&'static str] {
+        match self {
+            Self::Rust => RUST_ROOT_FILES,
+            Self::Node => NODE_ROOT_FILES,
+            Self::Flutter => FLUTTER_ROOT_FILES,
+        }
+    }
+
+    fn source_dir(self) -> &'static str {
+        match self {
+            Self::Rust | Self::Node => "src/",
+            Self::Flutter => "lib/",
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static FILTER_BUILDS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Per-root filtering decisions, resolved from the config once and shared by every
+/// per-file check, including the parallel read tasks.
+struct FileFilter<'a> {
+    root: &'a Path,
+    project_types: Vec<ProjectType>,
+    skips: Vec<&'a str>,
+    /// `--skip` entries written `!path`: path prefixes included regardless of the rules.
+    explicit: Vec<&'a str>,
+    only: &'a [String],
+    language_only: Option<&'a str>,
+    /// Matcher for `--ignore-file`, rooted at `root`.
+    ignore_file: Option<Gitignore>,
+}
+
+impl<'a> FileFilter<'a> {
+    fn new(config: &'a ExtractConfig) -> Result<Self> {
+        #[cfg(test)]
+        FILTER_BUILDS.with(|builds| builds.set(builds.get() + 1));
+        let (explicit, skips) = config
+            .extra_ignores
+            .iter()
+            .map(String::as_str)
+            .partition::<Vec<_>, _>(|entry| entry.starts_with('!'));
+        let ignore_file = match &config.ignore_file {
+            Some(path) => {
+                let mut builder = GitignoreBuilder::new(&config.root);
+                if let Some(err) = builder.add(path) {
+                    return Err(err)
+                        .with_context(|| format!("failed to load ignore file: {:?}", path));
+                }
+                Some(builder.build()?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            root: &config.root,
+            project_types: config
+                .project_type
+                .as_deref()
+                .map(|hint| ProjectType::resolve(hint, &config.root))
+                .unwrap_or_default(),
+            skips,
+            explicit: explicit.iter().map(|entry| &entry[1..]).collect(),
+            only: &config.only,
+            language_only: config.language_only.as_deref(),
+            ignore_file,
+        })
+    }
+
+    /// Decides whether a walked file is extracted. `--only`, when given, restricts
+    /// everything: files outside every prefix are excluded. The remaining sources are
+    /// consulted in order of precedence and the first one with an opinion wins:
+    ///
+    /// 1. Explicit includes (`--skip !path`): kept whatever the rules below say.
+    /// 2. `--skip` entries: excluded.
+    /// 3. `--ignore-file`: excluded by its patterns, or kept when a `!` pattern
+    ///    re-includes it (overriding .gitignore).
+    /// 4. `.gitignore` and the other walker ignores (`ignored`): excluded.
+    ///
+    /// Anything left is kept when the include rules ([`Self::includes`]) accept it.
+    fn should_include_path(&self, path: &Path, ignored: bool) -> bool {
+        if !self.is_selected(path) {
+            return false;
+        }
+        let rel = path.strip_prefix(self.root).unwrap_or(path);
+        if self
+            .explicit
+            .iter()
+            .any(|prefix| rel.starts_with(prefix.trim_end_matches('/')))
+        {
+            return true;
+        }
+        if self.is_skipped(path) {
+            return false;
+        }
+        match self
+            .ignore_file
+            .as_ref()
+            .map(|gi| gi.matched_path_or_any_parents(path, false))
+        {
+            Some(ignore::Match::Ignore(_)) => return false,
+            Some(ignore::Match::Whitelist(_)) => return self.includes(path),
+            _ => {}
+        }
+        !ignored && self.includes(path)
+    }
+
+    /// Whether any source can include files that the walker's ignores drop.
+    fn has_overrides(&self) -> bool {
+        !self.explicit.is_empty()
+            || self
+                .ignore_file
+                .as_ref()
+                .is_some_and(|gi| gi.num_whitelists() > 0)
+    }
+
+    /// Path prefixes that bound the files [`Self::has_overrides`] may bring back, or
+    /// `None` when they can be anywhere (the ignore file re-includes something).
+    fn override_prefixes(&self) -> Option<Vec<PathBuf>> {
+        if self
+            .ignore_file
+            .as_ref()
+            .is_some_and(|gi| gi.num_whitelists() > 0)
+        {
+            return None;
+        }
+        Some(
+            self.explicit
+                .iter()
+                .map(|prefix| PathBuf::from(prefix.trim_end_matches('/')))
+                .collect(),
+        )
+    }
+
+    /// Decide inclusion by the union of the project kinds' rules (if any) or by extension.
+    fn includes(&self, path: &Path) -> bool {
+        let rel = path.strip_prefix(self.root).unwrap_or(path);
+        let s = rel.to_string_lossy();
+
+        if s == "Cargo.toml" || s == "pubspec.yaml" || s == "package.json" {
+            return true;
+        }
+        if parser::known_basename_lang(&s).is_some() {
+            return true;
+        }
+
+        if !self.project_types.is_empty() {
+            return self
+                .project_types
+                .iter()
+                .any(|pt| pt.root_files().contains(&&*s) || s.starts_with(pt.source_dir()));
+        }
+
+        matches!(
+            path.extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase)
+                .as_deref(),
+            Some("rs" | "toml" | "json" | "js" | "ts" | "dart")
+        )
+    }
+
+    /// Whether `path` matches one of the `--skip` entries (a path prefix or any component).
+    fn is_skipped(&self, path: &Path) -> bool {
+        let rel = path.strip_prefix(self.root).unwrap_or(path);
+        let rel_str = rel.to_string_lossy();
+        self.skips
+            .iter()
+            .any(|pat| rel_str.starts_with(pat) || rel.components().any(|c| c.as_os_str() == *pat))
+    }
+
+    /// Whether `path` lies under one of the `--only` prefixes (always true when none are given).
+    fn is_selected(&self, path: &Path) -> bool {
+        if self.only.is_empty() {
+            return true;
+        }
+        let rel = path.strip_prefix(self.root).unwrap_or(path);
+        self.only
+            .iter()
+            .any(|prefix| rel.starts_with(prefix.trim_end_matches('/')))
+    }
+
+    /// Whether a file with this fence language may be embedded.
+    fn accepts_language(&self, lang: &str) -> bool {
+        self.language_only.is_none_or(|only| only == lang)
+    }
+}
+
+/// The path shown for `path`: relative to the root, after the `--anonymize` replacements.
+fn display_path(path: &Path, config: &ExtractConfig) -> String {
+    anonymize(&rel_string(path, &config.root), &config.anonymize)
+}
+
+/// `files` moved to their [`display_path`]s under the root, for building the tree.
+fn displayed_paths(files: &[PathBuf], config: &ExtractConfig) -> Vec<PathBuf> {
+    files
+        .iter()
+        .map(|path| config.root.join(display_path(path, config)))
+        .collect()
+}
+
+/// Applies each `(from, to)` substring replacement to `text`, in order.
+fn anonymize(text: &str, rules: &[(String, String)]) -> String {
+    rules
+        .iter()
+        .fold(text.to_string(), |text, (from, to)| text.replace(from, to))
+}
+
+/// Path of `path` relative to `root`, with `/` separators.
+fn rel_string(path: &Path, root: &Path) -> String {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    rel.to_string_lossy().replace('\\', "/")
+}
+
+/// Build the tree as a nested Markdown list whose file entries link to their sections.
+fn build_linked_tree(files: &[PathBuf], root: &Path, anchors: &HashMap<PathBuf, String>) -> String {
+    let mut top = TreeNode::default();
+    for path in files {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        let mut node = &mut top;
+        for part in rel.iter() {
+            node = node
+                .children
+                .entry(part.to_string_lossy().into_owned())
+                .or_default();
+        }
+        node.anchor = anchors.get(path).cloned();
+    }
+
+    let mut tree = String::new();
+    top.render_linked("", &mut tree);
+    tree
+}
+
+/// Build an ASCII tree of the file paths by grouping them into a nested map first,
+/// so each path is visited once regardless of how wide a directory is.
+fn build_tree(files: &[PathBuf], root: &Path) -> String {
+    let mut top = TreeNode::default();
+    for path in files {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        let mut node = &mut top;
+        for part in rel.iter() {
+            node = node
+                .children
+                .entry(part.to_string_lossy().into_owned())
+                .or_default();
+        }
+    }
+
+    let mut tree = String::new();
+    top.render("", &mut tree);
+    tree
+}
+
+/// One directory level of the rendered tree; files are nodes without children.
+#[derive(Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    /// Section anchor of a file node, used by the linked tree.
+    anchor: Option<String>,
+}
+
+impl TreeNode {
+    fn render(&self, prefix: &str, out: &mut String) {
+        let count = self.children.len();
+        for (i, (name, child)) in self.children.iter().enumerate() {
+            let last = i + 1 == count;
+            out.push_str(prefix);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(name);
+            out.push('\n');
+            if !child.children.is_empty() {
+                let nested = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                child.render(&nested, out);
+            }
+        }
+    }
+
+    fn render_linked(&self, indent: &str, out: &mut String) {
+        for (name, child) in &self.children {
+            match &child.anchor {
+                Some(anchor) => out.push_str(&format!("{}- [{}](#{})\n", indent, name, anchor)),
+                // a file listed without a section (e.g. over the size limit)
+                None if child.children.is_empty() => {
+                    out.push_str(&format!("{}- {}\n", indent, name))
+                }
+                None => out.push_str(&format!("{}- {}/\n", indent, name)),
+            }
+            child.render_linked(&format!("{}  ", indent), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, rel: &str, content: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn config_for(root: &Path) -> ExtractConfig {
+        ExtractConfig {
+            root: root.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// The whole [`extract_to_writer`] output as a string.
+    fn extract_to_markdown(config: ExtractConfig) -> Result<String> {
+        let mut out = Vec::new();
+        extract_to_writer(config, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn test_inline_readmes_precede_directory_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\nname = \"demo\"\n");
+        write(tmp.path(), "src/README.md", "The source tree lives here.\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            inline_readmes: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        let prose = md.find("The source tree lives here.").unwrap();
+        let main = md.find("### <file> src/main.rs </file>").unwrap();
+        let cargo = md.find("### <file> Cargo.toml </file>").unwrap();
+        assert!(cargo < prose && prose < main);
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(!md.contains("The source tree lives here."));
+    }
+
+    #[test]
+    fn test_inline_readme_of_directory_with_only_subdirectories() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\nname = \"demo\"\n");
+        write(tmp.path(), "docs/README.md", "Docs overview.\n");
+        write(tmp.path(), "docs/api/README.md", "API reference.\n");
+        write(tmp.path(), "docs/api/x.rs", "pub fn x() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            inline_readmes: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        let docs = md.find("Docs overview.").unwrap();
+        let api = md.find("API reference.").unwrap();
+        let x = md.find("### <file> docs/api/x.rs </file>").unwrap();
+        assert!(docs < api && api < x);
+        assert_eq!(md.matches("Docs overview.").count(), 1);
+    }
+
+    #[test]
+    fn test_min_file_size_drops_small_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/mod.rs", "//");
+        write(
+            tmp.path(),
+            "src/main.rs",
+            "fn main() { println!(\"hi\"); }\n",
+        );
+
+        let md = extract_to_markdown(ExtractConfig {
+            min_file_size: Some(10),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("### <file> src/mod.rs </file>"));
+        // Still listed in the tree.
+        assert!(md.contains("── mod.rs\n"));
+    }
+
+    #[test]
+    fn test_project_type_root_config_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "package.json", "{}");
+        write(tmp.path(), "tsconfig.json", "{}");
+        write(tmp.path(), ".npmrc", "save-exact=true\n");
+        write(tmp.path(), "analysis_options.yaml", "linter:\n");
+        write(tmp.path(), "src/index.ts", "export {};\n");
+        write(tmp.path(), ".hidden/secret.json", "{}");
+
+        let md = extract_to_markdown(ExtractConfig {
+            project_type: Some("node".into()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> tsconfig.json </file>"));
+        assert!(md.contains("### <file> .npmrc </file>"));
+        assert!(md.contains("### <file> src/index.ts </file>"));
+        assert!(!md.contains("analysis_options.yaml"));
+        assert!(!md.contains("secret.json"));
+
+        let md = extract_to_markdown(ExtractConfig {
+            project_type: Some("flutter".into()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> analysis_options.yaml </file>"));
+        assert!(!md.contains("tsconfig.json"));
+    }
+
+    #[test]
+    fn test_project_types_union_in_polyglot_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+        write(tmp.path(), "pubspec.yaml", "name: app\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "lib/main.dart", "void main() {}\n");
+        write(tmp.path(), "tools/gen.rs", "fn gen() {}\n");
+
+        for hint in ["auto", "rust,flutter"] {
+            let md = extract_to_markdown(ExtractConfig {
+                project_type: Some(hint.into()),
+                ..config_for(tmp.path())
+            })
+            .unwrap();
+            assert!(md.contains("### <file> src/main.rs </file>"), "{}", hint);
+            assert!(md.contains("### <file> lib/main.dart </file>"), "{}", hint);
+            assert!(md.contains("### <file> pubspec.yaml </file>"), "{}", hint);
+            assert!(!md.contains("tools/gen.rs"), "{}", hint);
+        }
+
+        assert_eq!(
+            ProjectType::resolve("auto,rust", tmp.path()),
+            [ProjectType::Rust, ProjectType::Flutter]
+        );
+    }
+
+    #[test]
+    fn test_default_lang_for_unmapped_extensions() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "pubspec.yaml", "name: app\n");
+        write(tmp.path(), "lib/main.dart", "void main() {}\n");
+
+        let extract = |default_lang: Option<&str>| {
+            extract_to_markdown(ExtractConfig {
+                project_type: Some("flutter".into()),
+                default_lang: default_lang.map(String::from),
+                ..config_for(tmp.path())
+            })
+            .unwrap()
+        };
+        let md = extract(None);
+        assert!(md.contains("### <file> pubspec.yaml </file>\n```\nname: app"));
+
+        let md = extract(Some("text"));
+        assert!(md.contains("### <file> pubspec.yaml </file>\n```text\nname: app"));
+        assert!(
+            md.contains("```dart\nvoid main() {}"),
+            "mapped languages are kept"
+        );
+    }
+
+    #[test]
+    fn test_context_lines_emit_only_matching_regions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let body: String = (1..=12).map(|i| format!("line {}\n", i)).collect();
+        write(
+            tmp.path(),
+            "src/lib.rs",
+            &body.replace("line 3\n", "// TODO 3\n"),
+        );
+        write(tmp.path(), "src/other.rs", "pub fn other() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            content_filter: Some(Regex::new("TODO|line 10").unwrap()),
+            context_lines: Some(1),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(
+            md.contains("```rust\nline 2\n// TODO 3\nline 4\n...\nline 9\nline 10\nline 11\n```")
+        );
+        assert!(!md.contains("line 1\n"));
+        assert!(!md.contains("line 12"));
+        assert!(!md.contains("### <file> src/other.rs </file>"));
+    }
+
+    #[test]
+    fn test_content_filter_embeds_matching_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/safe.rs", "pub fn safe() {}\n");
+        write(tmp.path(), "src/ptr.rs", "pub fn raw() { unsafe {} }\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            content_filter: Some(Regex::new(r"\bunsafe\b").unwrap()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/ptr.rs </file>"));
+        assert!(!md.contains("### <file> src/safe.rs </file>"));
+        assert!(md.contains("── safe.rs\n"));
+    }
+
+    #[test]
+    fn test_uppercase_extensions_are_embedded() {
+        assert_eq!(lang_for_extension("JSON"), "json");
+        assert_eq!(lang_for_path(Path::new("src/MAIN.RS")), "rust");
+
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Config.JSON", "{}\n");
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(md.contains("### <file> Config.JSON </file>\n```json\n{}\n```"));
+    }
+
+    #[test]
+    fn test_language_only_embeds_single_language() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\nname = \"demo\"\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "web/app.js", "console.log(1);\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            language_only: Some("rust".into()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("### <file> Cargo.toml </file>"));
+        assert!(!md.contains("### <file> web/app.js </file>"));
+        assert!(md.contains("── Cargo.toml\n"));
+    }
+
+    #[test]
+    fn test_anchors_are_unique_per_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/a/mod.rs", "pub mod x;\n");
+        write(tmp.path(), "src/b/mod.rs", "pub mod y;\n");
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(md.contains("<a id=\"src-a-mod-rs\"></a>\n### <file> src/a/mod.rs </file>"));
+        assert!(md.contains("<a id=\"src-b-mod-rs\"></a>\n### <file> src/b/mod.rs </file>"));
+
+        let mut anchors = Anchors::default();
+        assert_eq!(anchors.anchor("src/a_b.rs"), "src-a-b-rs");
+        assert_eq!(anchors.anchor("src/a-b.rs"), "src-a-b-rs-1");
+        assert_eq!(anchors.anchor("src/a.b.rs"), "src-a-b-rs-2");
+    }
+
+    #[test]
+    fn test_hashes_annotate_sections() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/hello.rs", "hello\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            hashes: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains(
+            "<a id=\"src-hello-rs\"></a> <!-- sha256: 5891b5b5 -->\n### <file> src/hello.rs </file>"
+        ));
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(!md.contains("sha256"));
+    }
+
+    #[test]
+    fn test_module_map_annotates_rust_sections() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(
+            tmp.path(),
+            "src/lib.rs",
+            "mod foo;\npub mod bar;\nuse crate::foo::Thing;\n",
+        );
+        write(tmp.path(), "Cargo.toml", "[package]\nname = \"demo\"\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            module_map: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains(
+            "<a id=\"src-lib-rs\"></a> <!-- declares: foo, bar; uses: crate::foo -->\n### <file> src/lib.rs </file>"
+        ));
+        assert!(md.contains("<a id=\"cargo-toml\"></a>\n"));
+    }
+
+    #[test]
+    fn test_fenced_info_path_widens_fence() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/doc.rs", "//! ```\n//! demo\n//! ```\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            pattern: Some(MdPatternCli::FencedInfoPath),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("````rust path=src/doc.rs\n//! ```\n//! demo\n//! ```\n````\n"));
+    }
+
+    #[test]
+    fn test_parent_gitignore_applies_to_subdirectory() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".git")).unwrap();
+        write(tmp.path(), ".gitignore", "generated.rs\n");
+        write(tmp.path(), "crate/src/lib.rs", "pub fn lib() {}\n");
+        write(tmp.path(), "crate/src/generated.rs", "pub fn gen() {}\n");
+
+        let md = extract_to_markdown(config_for(&tmp.path().join("crate"))).unwrap();
+        assert!(md.contains("### <file> src/lib.rs </file>"));
+        assert!(!md.contains("generated.rs"));
+    }
+
+    #[test]
+    fn test_include_ignored_walks_gitignored_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".git")).unwrap();
+        write(tmp.path(), ".git/config", "[core]\n");
+        write(tmp.path(), ".gitignore", "generated.rs\n");
+        write(tmp.path(), "src/lib.rs", "pub fn lib() {}\n");
+        write(tmp.path(), "src/generated.rs", "pub fn gen() {}\n");
+        write(tmp.path(), ".cache/out.rs", "pub fn cached() {}\n");
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(!md.contains("generated.rs"));
+        assert!(!md.contains(".cache/out.rs"));
+
+        let md = extract_to_markdown(ExtractConfig {
+            include_ignored: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/generated.rs </file>"));
+        assert!(md.contains(".cache/out.rs"));
+        assert!(!md.contains("[core]"), ".git is never walked");
+    }
+
+    #[test]
+    fn test_extract_to_writer_streams_tree_and_sections() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\nname = \"demo\"\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/lib.rs", "pub fn lib() {}\n");
+
+        let mut streamed = Vec::new();
+        extract_to_writer(config_for(tmp.path()), &mut streamed).unwrap();
+        let md = String::from_utf8(streamed).unwrap();
+        let expected = "# Project structure\n\n```\n\
+                        ├── Cargo.toml\n\
+                        └── src\n    ├── lib.rs\n    └── main.rs\n```\n\n\
+                        <a id=\"cargo-toml\"></a>\n### <file> Cargo.toml </file>\n\
+                        ```toml\n[package]\nname = \"demo\"\n```\n\n\
+                        <a id=\"src-lib-rs\"></a>\n### <file> src/lib.rs </file>\n\
+                        ```rust\npub fn lib() {}\n```\n\n\
+                        <a id=\"src-main-rs\"></a>\n### <file> src/main.rs </file>\n\
+                        ```rust\nfn main() {}\n```\n\n";
+        assert_eq!(md, expected);
+    }
+
+    #[test]
+    fn test_parallel_reads_preserve_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..(READ_BATCH + 20) {
+            write(
+                tmp.path(),
+                &format!("src/m{:04}.rs", i),
+                &format!("pub fn f{}() {{}}\n", i),
+            );
+        }
+
+        let extract_with_threads = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| extract_to_markdown(config_for(tmp.path())).unwrap())
+        };
+        let sequential = extract_with_threads(1);
+        let parallel = extract_with_threads(8);
+        assert_eq!(sequential, parallel);
+
+        let first = parallel.find("src/m0000.rs </file>").unwrap();
+        let last = parallel
+            .find(&format!("src/m{:04}.rs </file>", READ_BATCH + 19))
+            .unwrap();
+        assert!(first < last);
+    }
+
+    /// Counts how many source files were actually read.
+    #[derive(Default)]
+    struct CountingReader {
+        reads: std::sync::atomic::AtomicUsize,
+    }
+
+    impl SourceReader for CountingReader {
+        fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+            self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            fs::read_to_string(path)
+        }
+    }
+
+    #[test]
+    fn test_cache_skips_unchanged_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("project");
+        write(&root, "src/main.rs", "fn main() {}\n");
+        write(&root, "src/lib.rs", "pub fn lib() {}\n");
+        let cached_config = || ExtractConfig {
+            cache_dir: Some(tmp.path().join("cache")),
+            hashes: true,
+            ..config_for(&root)
+        };
+
+        let reader = CountingReader::default();
+        let mut first = Vec::new();
+        extract_with_reader(cached_config(), &mut first, &reader).unwrap();
+        assert_eq!(reader.reads.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        let reader = CountingReader::default();
+        let mut second = Vec::new();
+        extract_with_reader(cached_config(), &mut second, &reader).unwrap();
+        assert_eq!(reader.reads.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(first, second);
+
+        // Different render settings must not reuse the cached sections.
+        let reader = CountingReader::default();
+        let config = ExtractConfig {
+            pattern: Some(MdPatternCli::Hash),
+            ..cached_config()
+        };
+        extract_with_reader(config, Vec::new(), &reader).unwrap();
+        assert_eq!(reader.reads.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_build_tree_nests_with_connectors() {
+        let root = Path::new("/project");
+        let files: Vec<PathBuf> = [
+            "Cargo.toml",
+            "src/main.rs",
+            "src/util/mod.rs",
+            "tests/it.rs",
+        ]
+        .iter()
+        .map(|rel| root.join(rel))
+        .collect();
+        let expected = indoc::indoc! {"
+            ├── Cargo.toml
+            ├── src
+            │   ├── main.rs
+            │   └── util
+            │       └── mod.rs
+            └── tests
+                └── it.rs
+        "};
+        assert_eq!(build_tree(&files, root), expected);
+    }
+
+    #[test]
+    fn test_build_tree_wide_directory() {
+        let root = Path::new("/project");
+        let mut files: Vec<PathBuf> = (0..1000)
+            .map(|i| root.join(format!("dir_{:02}/file_{:04}.rs", i % 10, i)))
+            .collect();
+        files.sort();
+
+        let started = std::time::Instant::now();
+        let tree = build_tree(&files, root);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(lines.len(), 1010);
+        assert_eq!(lines[0], "├── dir_00");
+        assert_eq!(lines[1], "│   ├── file_0000.rs");
+        assert_eq!(lines[100], "│   └── file_0990.rs");
+        assert_eq!(lines[909], "└── dir_09");
+        assert_eq!(lines[1009], "    └── file_0999.rs");
+    }
+
+    #[test]
+    fn test_binary_and_oversized_files_are_not_fully_read() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        let mut blob = vec![0u8; 4 * 1024 * 1024];
+        blob[..6].copy_from_slice(b"binary");
+        fs::write(tmp.path().join("src/blob.rs"), &blob).unwrap();
+
+        let reader = CountingReader::default();
+        let mut out = Vec::new();
+        extract_with_reader(config_for(tmp.path()), &mut out, &reader).unwrap();
+        let md = String::from_utf8(out).unwrap();
+        assert_eq!(reader.reads.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("### <file> src/blob.rs </file>"));
+        assert!(md.contains("── blob.rs\n"));
+
+        write(tmp.path(), "src/big.rs", &"// padding\n".repeat(1000));
+        let reader = CountingReader::default();
+        let config = ExtractConfig {
+            max_file_size: Some(1024),
+            ..config_for(tmp.path())
+        };
+        let mut out = Vec::new();
+        extract_with_reader(config, &mut out, &reader).unwrap();
+        let md = String::from_utf8(out).unwrap();
+        assert_eq!(reader.reads.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(!md.contains("### <file> src/big.rs </file>"));
+    }
+
+    #[test]
+    fn test_file_filter_built_once_per_run() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            write(tmp.path(), &format!("src/m{}.rs", i), "pub fn f() {}\n");
+        }
+
+        let before = FILTER_BUILDS.with(|builds| builds.get());
+        let md = extract_to_markdown(ExtractConfig {
+            project_type: Some("rust".into()),
+            extra_ignores: vec!["src/m1.rs".into()],
+            language_only: Some("rust".into()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert_eq!(FILTER_BUILDS.with(|builds| builds.get()) - before, 1);
+        assert!(md.contains("### <file> src/m0.rs </file>"));
+        assert!(!md.contains("### <file> src/m1.rs </file>"));
+    }
+
+    #[test]
+    fn test_only_restricts_to_path_prefixes() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+        write(tmp.path(), "src/api/routes.rs", "pub fn routes() {}\n");
+        write(tmp.path(), "src/api_old.rs", "pub fn old() {}\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            only: vec!["src/api/".into(), "Cargo.toml".into()],
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> Cargo.toml </file>"));
+        assert!(md.contains("### <file> src/api/routes.rs </file>"));
+        assert!(!md.contains("api_old.rs"));
+        assert!(!md.contains("main.rs"));
+    }
+
+    /// A tree whose `src/gen/` is gitignored, extracted with `config` applied on top;
+    /// returns the embedded paths.
+    fn precedence_fixture(config: impl FnOnce(&Path) -> ExtractConfig) -> Vec<String> {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), ".gitignore", "gen/\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/util.rs", "pub fn util() {}\n");
+        write(tmp.path(), "src/gen/a.rs", "pub fn a() {}\n");
+        write(tmp.path(), "src/gen/b.rs", "pub fn b() {}\n");
+        write(tmp.path(), ".extractignore", "!gen/\nsrc/gen/b.rs\n");
+        let md = extract_to_markdown(config(tmp.path())).unwrap();
+        md.lines()
+            .filter_map(|line| line.strip_prefix("### <file> "))
+            .map(|line| line.trim_end_matches(" </file>").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_precedence_ignore_file_over_gitignore() {
+        assert_eq!(
+            precedence_fixture(config_for),
+            ["src/main.rs", "src/util.rs"]
+        );
+        let paths = precedence_fixture(|root| ExtractConfig {
+            ignore_file: Some(root.join(".extractignore")),
+            ..config_for(root)
+        });
+        assert_eq!(paths, ["src/gen/a.rs", "src/main.rs", "src/util.rs"]);
+    }
+
+    #[test]
+    fn test_precedence_skip_over_ignore_file() {
+        let paths = precedence_fixture(|root| ExtractConfig {
+            ignore_file: Some(root.join(".extractignore")),
+            extra_ignores: vec!["src/gen/a.rs".into(), "util.rs".into()],
+            ..config_for(root)
+        });
+        assert_eq!(paths, ["src/main.rs"]);
+    }
+
+    #[test]
+    fn test_precedence_explicit_include_over_skip_and_ignores() {
+        let paths = precedence_fixture(|root| ExtractConfig {
+            ignore_file: Some(root.join(".extractignore")),
+            extra_ignores: vec!["src".into(), "!src/gen/b.rs".into(), "!src/util.rs".into()],
+            ..config_for(root)
+        });
+        assert_eq!(paths, ["src/gen/b.rs", "src/util.rs"]);
+    }
+
+    #[test]
+    fn test_precedence_only_restricts_the_chain() {
+        let paths = precedence_fixture(|root| ExtractConfig {
+            only: vec!["src".into()],
+            ..config_for(root)
+        });
+        assert_eq!(paths, ["src/main.rs", "src/util.rs"]);
+        let paths = precedence_fixture(|root| ExtractConfig {
+            only: vec!["src".into()],
+            extra_ignores: vec!["src/gen".into(), "util.rs".into()],
+            ..config_for(root)
+        });
+        assert_eq!(paths, ["src/main.rs"]);
+        let paths = precedence_fixture(|root| ExtractConfig {
+            only: vec!["src/gen/".into()],
+            extra_ignores: vec!["!src/gen/b.rs".into(), "!src/util.rs".into()],
+            ..config_for(root)
+        });
+        assert_eq!(paths, ["src/gen/b.rs"]);
+    }
+
+    #[test]
+    fn test_only_keeps_aiignore() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), ".aiignore", "src/secrets.rs\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(
+            tmp.path(),
+            "src/secrets.rs",
+            "const KEY: &str = \"hunter2\";\n",
+        );
+
+        let md = extract_to_markdown(ExtractConfig {
+            only: vec!["src".into()],
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_custom_ignore_file_excludes_matches() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), ".extractignore", "*_secret.rs\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(
+            tmp.path(),
+            "src/api_secret.rs",
+            "pub const KEY: &str = \"k\";\n",
+        );
+
+        let md = extract_to_markdown(ExtractConfig {
+            ignore_file: Some(tmp.path().join(".extractignore")),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("api_secret.rs"));
+
+        let missing = extract_to_markdown(ExtractConfig {
+            ignore_file: Some(tmp.path().join("missing-ignore")),
+            ..config_for(tmp.path())
+        });
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_tracked_only_excludes_untracked_files() {
+        let git_available = Command::new("git").arg("--version").output().is_ok();
+        if !git_available {
+            return;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let not_a_repo = extract_to_markdown(ExtractConfig {
+            tracked_only: true,
+            ..config_for(tmp.path())
+        });
+        assert!(not_a_repo.is_err());
+
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        write(tmp.path(), "src/tracked.rs", "pub fn tracked() {}\n");
+        write(tmp.path(), "src/scratch.rs", "pub fn scratch() {}\n");
+        git(&["add", "src/tracked.rs"]);
+
+        let md = extract_to_markdown(ExtractConfig {
+            tracked_only: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/tracked.rs </file>"));
+        assert!(!md.contains("scratch.rs"));
+
+        write(tmp.path(), "src/README.md", "Untracked notes.\n");
+        let md = extract_to_markdown(ExtractConfig {
+            tracked_only: true,
+            inline_readmes: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(!md.contains("Untracked notes."));
+    }
+
+    #[test]
+    fn test_fence_aliases_rename_languages() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            fence_aliases: BTreeMap::from([("rust".into(), "rs".into())]),
+            language_only: Some("rust".into()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("```rs\nfn main() {}\n```"));
+        assert!(!md.contains("```rust"));
+    }
+
+    /// Fails every read of files named `locked.rs`.
+    struct LockedReader;
+
+    impl SourceReader for LockedReader {
+        fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+            self.read_prefix(path, usize::MAX)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        }
+
+        fn read_prefix(&self, path: &Path, limit: usize) -> std::io::Result<Vec<u8>> {
+            if path.ends_with("locked.rs") {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "permission denied",
+                ));
+            }
+            let mut prefix = Vec::new();
+            fs::File::open(path)?
+                .take(limit as u64)
+                .read_to_end(&mut prefix)?;
+            Ok(prefix)
+        }
+    }
+
+    #[test]
+    fn test_header_template_renders_custom_heading() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {\n    run();\n}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            header_template: Some("{path} ({lang}, {lines} lines, {size} bytes) {hash}".into()),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        let hash = short_hash(b"fn main() {\n    run();\n}\n");
+        assert!(md.contains(&format!(
+            "### src/main.rs (rust, 3 lines, 25 bytes) {}\n```rust\nfn main() {{",
+            hash
+        )));
+        assert!(!md.contains("<file>"));
+    }
+
+    #[test]
+    fn test_ai_ignore_files_are_honoured() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(
+            tmp.path(),
+            "src/secrets.rs",
+            "pub const KEY: &str = \"k\";\n",
+        );
+        write(tmp.path(), "src/gen/schema.rs", "pub struct Schema;\n");
+        write(tmp.path(), ".aiignore", "secrets.rs\n");
+        write(tmp.path(), "src/.cursorignore", "gen/\n");
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("secrets.rs"));
+        assert!(!md.contains("schema.rs"));
+
+        let md = extract_to_markdown(ExtractConfig {
+            include_ignored: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/secrets.rs </file>"));
+    }
+
+    #[test]
+    fn test_max_line_length_truncates_long_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let long = format!("const BLOB = \"{}\";", "é".repeat(100));
+        write(
+            tmp.path(),
+            "dist/app.js",
+            &format!("let a = 1;\n{}\nlet b = 2;\n", long),
+        );
+
+        let md = extract_to_markdown(ExtractConfig {
+            max_line_length: Some(20),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains(
+            "```javascript\nlet a = 1;\nconst BLOB = \"éééééé… [truncated]\nlet b = 2;\n```"
+        ));
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(md.contains(&long));
+    }
+
+    #[test]
+    fn test_modified_within_excludes_older_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/old.rs", "pub fn old() {}\n");
+        let two_days_ago = SystemTime::now() - Duration::from_secs(48 * 60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(tmp.path().join("src/old.rs"))
+            .and_then(|file| file.set_modified(two_days_ago))
+            .unwrap();
+
+        let md = extract_to_markdown(ExtractConfig {
+            modified_within: Some(Duration::from_secs(24 * 60 * 60)),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(!md.contains("old.rs"));
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(md.contains("### <file> src/old.rs </file>"));
+    }
+
+    #[test]
+    fn test_binary_files_embedded_as_base64() {
+        // A 1x1 transparent PNG.
+        const PNG: &[u8] = b"\x89\x50\x4e\x47\x0d\x0a\x1a\x0a\x00\x00\x00\x0d\x49\x48\x44\x52\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x1f\x15\xc4\x89\x00\x00\
+        \x00\x0d\x49\x44\x41\x54\x78\x9c\x63\xf8\xcf\xc0\xf0\x1f\x00\x05\x00\x01\xff\x89\x99\x3d\x1d\x00\x00\x00\x00\x49\x45\x4e\x44\xae\x42\x60\x82";
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        fs::write(tmp.path().join("src/icon.png"), PNG).unwrap();
+        let config = |binary| ExtractConfig {
+            project_type: Some("rust".into()),
+            binary,
+            ..config_for(tmp.path())
+        };
+
+        let md = extract_to_markdown(config(BinaryPolicy::Skip)).unwrap();
+        assert!(md.contains("── icon.png\n"));
+        assert!(!md.contains("### <file> src/icon.png </file>"));
+
+        let md = extract_to_markdown(config(BinaryPolicy::Base64)).unwrap();
+        assert!(md.contains(
+            "### <file> src/icon.png </file>\n```base64\n\
+             iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9\n\
+             HQAAAABJRU5ErkJggg==\n```\n\n\
+             *Binary file: 70 bytes, image/png, embedded as base64.*"
+        ));
+        assert!(md.contains("```rust\nfn main() {}"));
+    }
+
+    #[test]
+    fn test_base64_encode_padding_and_wrapping() {
+        assert_eq!(base64_encode(b""), "\n");
+        assert_eq!(base64_encode(b"f"), "Zg==\n");
+        assert_eq!(base64_encode(b"fo"), "Zm8=\n");
+        assert_eq!(base64_encode(b"foo"), "Zm9v\n");
+        let wrapped = base64_encode(&[0u8; 60]);
+        assert_eq!(wrapped.lines().map(str::len).collect::<Vec<_>>(), [76, 4]);
+    }
+
+    #[test]
+    fn test_on_read_error_policy() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/locked.rs", "pub fn locked() {}\n");
+
+        let failed = extract_with_reader(config_for(tmp.path()), Vec::new(), &LockedReader);
+        assert!(failed.is_err());
+
+        let config = ExtractConfig {
+            on_read_error: ReadErrorPolicy::Skip,
+            ..config_for(tmp.path())
+        };
+        let mut out = Vec::new();
+        extract_with_reader(config, &mut out, &LockedReader).unwrap();
+        let md = String::from_utf8(out).unwrap();
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(md.contains("── locked.rs\n"));
+        assert!(md.contains("*Skipped `src/locked.rs`: could not read it (permission denied).*"));
+        assert!(!md.contains("pub fn locked"));
+    }
+
+    #[test]
+    fn test_manifest_only_reads_no_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+
+        let reader = CountingReader::default();
+        let config = ExtractConfig {
+            manifest_only: true,
+            ..config_for(tmp.path())
+        };
+        let mut out = Vec::new();
+        extract_with_reader(config, &mut out, &reader).unwrap();
+        let md = String::from_utf8(out).unwrap();
+
+        assert_eq!(reader.reads.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert!(md.contains("── main.rs\n"));
+        assert!(md.contains("- `Cargo.toml` (10 bytes)\n- `src/main.rs` (13 bytes)\n"));
+        assert!(!md.contains("fn main"));
+        assert!(!md.contains("<file>"));
+    }
+
+    #[test]
+    fn test_anonymize_paths_and_content() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(
+            tmp.path(),
+            "acme_corp/src/lib.rs",
+            "// acme_corp internal\n",
+        );
+
+        let rules = vec![("acme_corp".to_string(), "company".to_string())];
+        let md = extract_to_markdown(ExtractConfig {
+            anonymize: rules.clone(),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("└── company\n    └── src\n        └── lib.rs\n"));
+        assert!(
+            md.contains("<a id=\"company-src-lib-rs\"></a>\n### <file> company/src/lib.rs </file>")
+        );
+        assert!(md.contains("// acme_corp internal"));
+
+        let md = extract_to_markdown(ExtractConfig {
+            anonymize: rules,
+            anonymize_content: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("// company internal"));
+        assert!(!md.contains("acme_corp"));
+    }
+
+    #[test]
+    fn test_anonymize_content_with_context_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(
+            tmp.path(),
+            "src/lib.rs",
+            "// acme_corp secret\n// TODO: rename\nfn a() {}\n\n\nfn b() {}\n",
+        );
+
+        let md = extract_to_markdown(ExtractConfig {
+            anonymize: vec![("acme_corp".to_string(), "company".to_string())],
+            anonymize_content: true,
+            content_filter: Some(Regex::new("TODO").unwrap()),
+            context_lines: Some(1),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("// company secret\n// TODO: rename\nfn a() {}"));
+        assert!(!md.contains("fn b()"));
+        assert!(!md.contains("acme_corp"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_note_exec_tags_executable_files() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "scripts/run.sh", "#!/bin/sh\necho hi\n");
+        write(tmp.path(), "scripts/env.sh", "export A=1\n");
+        let script = tmp.path().join("scripts/run.sh");
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let md = extract_to_markdown(ExtractConfig {
+            extra_ignores: vec!["!scripts".into()],
+            pattern: Some(MdPatternCli::Hash),
+            note_exec: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("### scripts/run.sh [executable]\n```"));
+        assert!(md.contains("### scripts/env.sh\n```"));
+
+        let md = extract_to_markdown(ExtractConfig {
+            extra_ignores: vec!["!scripts".into()],
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(!md.contains("[executable]"));
+    }
+
+    #[test]
+    fn test_tilde_fences() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            fence_char: FenceChar::Tilde,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("# Project structure\n\n~~~\n└── src\n"));
+        assert!(md.contains("### <file> src/main.rs </file>\n~~~rust\nfn main() {}\n~~~\n"));
+        assert!(!md.contains("```"));
+
+        let md = extract_to_markdown(ExtractConfig {
+            fence_char: FenceChar::Tilde,
+            pattern: Some(MdPatternCli::FencedInfoPath),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("~~~rust path=src/main.rs\nfn main() {}\n~~~\n"));
+    }
+
+    #[test]
+    fn test_line_ending_report() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/crlf.rs", "fn a() {}\r\nfn b() {}\r\n");
+        write(tmp.path(), "src/lf.rs", "fn a() {}\n");
+        write(tmp.path(), "src/mixed.rs", "fn a() {}\r\nfn b() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            line_ending_report: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains(
+            "# Line endings\n\n- `src/crlf.rs`: CRLF\n- `src/lf.rs`: LF\n- `src/mixed.rs`: mixed\n"
+        ));
+        assert!(md.contains("**Summary:** 1 LF, 1 CRLF, 1 mixed\n"));
+        assert!(!md.contains("fn a()"));
+        assert_eq!(EolStyle::detect("no newline"), EolStyle::None);
+    }
+
+    #[test]
+    fn test_linked_tree_links_to_sections() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/util/mod.rs", "pub mod x;\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            linked_tree: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        let expected = indoc::indoc! {"
+            - [Cargo.toml](#cargo-toml)
+            - src/
+              - [main.rs](#src-main-rs)
+              - util/
+                - [mod.rs](#src-util-mod-rs)
+        "};
+        assert!(md.contains(expected));
+        assert!(!md.contains("├──"));
+        for anchor in ["cargo-toml", "src-main-rs", "src-util-mod-rs"] {
+            assert!(md.contains(&format!("<a id=\"{}\"></a>", anchor)));
+        }
+    }
+
+    #[test]
+    fn test_linked_tree_skips_files_without_sections() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/big.rs", &"// padding\n".repeat(20));
+
+        let md = extract_to_markdown(ExtractConfig {
+            linked_tree: true,
+            max_file_size: Some(100),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("- src/\n  - big.rs\n  - [main.rs](#src-main-rs)\n"));
+        assert!(!md.contains("#src-big-rs"));
+        assert!(md.contains("<a id=\"src-main-rs\"></a>"));
+    }
+
+    #[test]
+    fn test_convert_code_tag_to_hash() {
+        let md = "<code path=\"src/main.rs\">\nfn main() {}\n</code>\n\n\
+                  <code path=\"Cargo.toml\">\n[package]\nname = \"demo\"\n</code>\n";
+        let converted = convert_markdown(md, Some(MdPatternCli::CodeTag), Some(MdPatternCli::Hash));
+        assert!(converted.contains("### src/main.rs\n```rust\nfn main() {}\n```"));
+        assert!(!converted.contains("<code"));
+
+        let original = parser::parse_content(md, Some(parser::MdPatternType::CodeTag));
+        let reparsed = parser::parse_content(&converted, Some(parser::MdPatternType::HashMarker));
+        assert_eq!(reparsed, original);
+    }
+
+    #[test]
+    fn test_nested_headings_follow_directory_depth() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/api/routes.rs", "pub fn routes() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            nested_headings: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("# Files\n"));
+        assert!(md.contains("### <file> Cargo.toml </file>"));
+        assert!(md.contains("## src/\n"));
+        assert!(md.contains("### <file> src/main.rs </file>"));
+        assert!(md.contains("### src/api/\n"));
+        assert!(md.contains("\n#### <file> src/api/routes.rs </file>"));
+
+        // The nested document still parses back to the same files.
+        let files = parser::parse_content(&md, Some(parser::MdPatternType::FileFence));
+        let paths: Vec<_> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["Cargo.toml", "src/api/routes.rs", "src/main.rs"]);
+    }
+
+    #[test]
+    fn test_minify_embeds_compact_code() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = "use std::fmt;   \n\n\n\nfn main() {  \n    println!(\"hi  \");\n}\n\n";
+        write(tmp.path(), "src/main.rs", src);
+        write(tmp.path(), "Cargo.toml", "[package]   \n\n\nname = \"x\"\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            minify: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(
+            md.contains("```rust\nuse std::fmt;\n\nfn main() {\n    println!(\"hi  \");\n}\n```")
+        );
+        assert!(md.contains("[package]   \n\n\nname"), "TOML is left as is");
+
+        let md = extract_to_markdown(ExtractConfig {
+            minify: true,
+            strip_imports: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("```rust\nfn main() {"));
+    }
+
+    #[test]
+    fn test_extracts_known_extensionless_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Makefile", "build:\n\tcargo build\n");
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "NOTES", "not a known file\n");
+
+        let md = extract_to_markdown(config_for(tmp.path())).unwrap();
+        assert!(
+            md.contains("### <file> Makefile </file>\n```makefile\nbuild:\n\tcargo build\n```")
+        );
+        assert!(!md.contains("NOTES"));
+
+        let files = parser::parse_content(&md, Some(parser::MdPatternType::FileFence));
+        assert!(files.iter().any(|f| f.path == "Makefile"));
+    }
+
+    #[test]
+    fn test_ndjson_emits_one_record_per_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "Cargo.toml", "[package]\nname = \"demo\"\n");
+        write(
+            tmp.path(),
+            "src/main.rs",
+            "fn main() {\n    println!(\"\\\"hi\\\"\");\n}\n",
+        );
+        write(tmp.path(), "src/lib.rs", "pub fn lib() {}\n");
+
+        let md = extract_to_markdown(ExtractConfig {
+            format: ExtractFormat::Ndjson,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        let records: Vec<serde_json::Value> = md
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2]["path"], "src/main.rs");
+        assert_eq!(records[2]["language"], "rust");
+        assert_eq!(
+            records[2]["content"],
+            "fn main() {\n    println!(\"\\\"hi\\\"\");\n}\n"
+        );
+        assert!(!md.contains("# Project structure"));
+    }
+
+    #[test]
+    fn test_from_list_keeps_list_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/lib.rs", "pub fn lib() {}\n");
+        write(tmp.path(), "Cargo.toml", "[package]\n");
+        write(tmp.path(), "notes/plan.txt", "ship it\n");
+        write(
+            tmp.path(),
+            "files.txt",
+            "# curated\nsrc/main.rs\nnotes/plan.txt\n\nsrc/lib.rs\n",
+        );
+
+        let md = extract_to_markdown(ExtractConfig {
+            from_list: Some(tmp.path().join("files.txt")),
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        let positions: Vec<usize> = ["src/main.rs", "notes/plan.txt", "src/lib.rs"]
+            .iter()
+            .map(|p| md.find(&format!("### <file> {} </file>", p)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(!md.contains("Cargo.toml"));
+
+        write(tmp.path(), "files.txt", "src/main.rs\nsrc/gone.rs\n");
+        let err = extract_to_markdown(ExtractConfig {
+            from_list: Some(tmp.path().join("files.txt")),
+            ..config_for(tmp.path())
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("src/gone.rs"));
+    }
+
+    #[test]
+    fn test_from_list_rejects_paths_outside_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "outside.txt", "secret\n");
+        write(tmp.path(), "root/src/main.rs", "fn main() {}\n");
+        let root = tmp.path().join("root");
+        let absolute = tmp.path().join("outside.txt");
+
+        for entry in [
+            "../outside.txt",
+            "src/../../outside.txt",
+            absolute.to_str().unwrap(),
+        ] {
+            write(&root, "files.txt", &format!("src/main.rs\n{}\n", entry));
+            let err = extract_to_markdown(ExtractConfig {
+                from_list: Some(root.join("files.txt")),
+                ..config_for(&root)
+            })
+            .unwrap_err();
+            assert!(err.to_string().contains(entry), "{}", err);
+        }
+
+        write(&root, "files.txt", "./src/../src/main.rs\n");
+        let md = extract_to_markdown(ExtractConfig {
+            from_list: Some(root.join("files.txt")),
+            ..config_for(&root)
+        })
+        .unwrap();
+        assert!(md.contains("fn main() {}"));
+    }
+
+    #[test]
+    fn test_asciidoc_wraps_files_in_source_blocks() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "src/main.rs", "fn main() {}\n");
+        write(tmp.path(), "src/lib.rs", "/*\n----\n*/\npub fn lib() {}\n");
+
+        let doc = extract_to_markdown(ExtractConfig {
+            format: ExtractFormat::AsciiDoc,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(doc.starts_with("= Project structure\n\n....\n"));
+        assert!(doc.contains("== src/main.rs\n\n[source,rust]\n----\nfn main() {}\n----\n"));
+        assert!(doc.contains(
+            "== src/lib.rs\n\n[source,rust]\n-----\n/*\n----\n*/\npub fn lib() {}\n-----\n"
+        ));
+        assert!(!doc.contains("```"));
+    }
+
+    #[test]
+    fn test_convert_honours_lang_directive() {
+        let md = "<!-- lang: toml -->\n<code path=\"build/settings.rs\">\n[settings]\n</code>\n";
+        let converted = convert_markdown(md, None, Some(MdPatternCli::Hash));
+        assert_eq!(
+            converted,
+            "### build/settings.rs\n```toml\n[settings]\n```\n\n"
+        );
+    }
+
+    #[test]
+    fn test_trim_trailing_ws_in_embedded_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = "fn main() {   \r\n    let s = \"a  b\";\t\n}  \n";
+        write(tmp.path(), "src/main.rs", src);
+
+        let md = extract_to_markdown(ExtractConfig {
+            trim_trailing_ws: true,
+            ..config_for(tmp.path())
+        })
+        .unwrap();
+        assert!(md.contains("```rust\nfn main() {\r\n    let s = \"a  b\";\n}\n```"));
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("src/main.rs")).unwrap(),
+            src
+        );
+    }
 }
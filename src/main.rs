@@ -1,24 +1,35 @@
+mod cache;
 mod execute;
 mod extra;
 mod extract;
-mod file_gen;
-mod parser;
-mod scanner;
+mod minify;
+mod module_map;
+mod semaphore;
+mod task_log;
 
 use clap::{Parser, ValueEnum};
-use execute::execute_project_if_needed;
-use extract::{ExtractConfig, extract_to_markdown};
+use execute::{ExecPool, SystemRunner};
+use extract::{ExtractConfig, extract_to_writer};
+use prk_mdgen::{file_gen, parser, scanner};
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
+use regex::Regex;
+use semaphore::Semaphore;
+use serde::Serialize;
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use task_log::TaskLog;
 
-#[derive(Parser)]
+/// Serialized as-is by `--dump-config`, so every flag shows up in the dump.
+#[derive(Parser, Serialize)]
 #[command(author, version, about)]
 struct Cli {
-    /// Choose an operation: sample, prompt, extract, or none (default).
+    /// Choose an operation: sample, prompt, extract, tree, convert, or none (default).
     #[arg(value_enum, default_value = "none")]
     command: CommandChoice,
 
@@ -30,29 +41,368 @@ struct Cli {
     #[arg(short, long)]
     execute: bool,
 
-    /// Force a specific Markdown pattern for parsing (e.g. code-tag, hash, delimiter, raw, file-code, file-fence).
-    #[arg(short, long, value_enum)]
-    pattern: Option<MdPatternCli>,
+    /// (sample, prompt) Wrap prose in the generated Markdown at this many characters.
+    #[arg(long, value_name = "N", default_value_t = extra::DEFAULT_WRAP_WIDTH)]
+    wrap_width: usize,
 
-    /// Optional project type hint for extraction (e.g. "rust", "flutter", "node").
+    /// Stop processing remaining Markdown files after the first failure.
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Maximum number of generated projects executed (or compile-checked) concurrently,
+    /// independent of parsing.
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    exec_jobs: usize,
+
+    /// Force specific Markdown patterns for parsing, comma-separated in priority order
+    /// (code-tag, hash, delimiter, raw, file-code, file-fence, fenced-info-path).
+    /// Extraction renders with the first one.
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    pattern: Vec<MdPatternCli>,
+
+    /// (convert) Pattern to parse the input Markdown with; all patterns if omitted.
+    #[arg(long, value_enum)]
+    from: Option<MdPatternCli>,
+
+    /// (convert) Pattern to re-emit file blocks in [default: file-fence].
+    #[arg(long, value_enum)]
+    to: Option<MdPatternCli>,
+
+    /// Optional project type hint for extraction: one or more of "rust", "flutter",
+    /// "node" (comma-separated), or "auto" to detect them from the root manifests.
     #[arg(long)]
     project_type: Option<String>,
 
-    /// Comma‑separated list of file or folder names to skip during extraction.
+    /// Comma‑separated list of file or folder names to skip during extraction; a `!path`
+    /// entry includes that path even if skipped or ignored.
     #[arg(long, value_delimiter = ',')]
     skip: Vec<String>,
+
+    /// Extraction: additional ignore file (gitignore syntax), e.g. `.extractignore`.
+    #[arg(long, value_name = "PATH")]
+    ignore_file: Option<PathBuf>,
+
+    /// Extraction: only include files under this path prefix (repeatable).
+    #[arg(long, value_name = "PREFIX")]
+    only: Vec<String>,
+
+    /// Make derived project names folder-friendly: spaces become `_` and characters
+    /// such as parentheses are dropped (`response (2)` -> `response_2`).
+    #[arg(long)]
+    sanitize_names: bool,
+
+    /// Fail a Markdown file (exit code 3) that yields more than N file blocks, instead
+    /// of generating them.
+    #[arg(long, value_name = "N")]
+    max_blocks: Option<usize>,
+
+    /// Merge the file blocks of every Markdown file into the single project NAME
+    /// instead of one project per file; `--on-duplicate` decides shared paths.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["split_on_h1", "parse_only"])]
+    merge_into: Option<String>,
+
+    /// Extensions of the Markdown files to process, comma-separated.
+    #[arg(
+        long,
+        value_name = "EXTS",
+        value_delimiter = ',',
+        default_value = "md,markdown,mdx"
+    )]
+    md_ext: Vec<String>,
+
+    /// Concatenate every fenced block under a single path heading into one file.
+    #[arg(long)]
+    merge_fences_under_heading: bool,
+
+    /// Choose a pattern per block in one pass over the document, so documents mixing
+    /// styles yield each block once instead of merging overlapping results.
+    #[arg(long)]
+    pattern_from_content: bool,
+    /// Extraction: emit each directory's README.md as prose before the first file under it.
+    /// Extraction: emit each directory's README.md as prose before its files.
+    #[arg(long)]
+    inline_readmes: bool,
+
+    /// Extraction: skip embedding files smaller than this many bytes (they stay in the tree).
+    #[arg(long, value_name = "BYTES")]
+    min_file_size: Option<u64>,
+
+    /// Extraction: never read files larger than this many bytes (they stay in the tree).
+    #[arg(long, value_name = "BYTES")]
+    max_file_size: Option<u64>,
+
+    /// Extraction: only embed files whose content matches this regex.
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    #[serde(serialize_with = "serialize_regex")]
+    content_filter: Option<Regex>,
+
+    /// Extraction: with --content-filter, embed only the matching lines plus N lines of
+    /// context around each (grep -C style) instead of whole files.
+    #[arg(long, value_name = "N", requires = "content_filter")]
+    context_lines: Option<usize>,
+
+    /// Extraction: only embed files of this language (e.g. "rust"); the tree lists everything.
+    #[arg(long, value_name = "LANG")]
+    language_only: Option<String>,
+
+    /// Extraction: annotate each file section with a short SHA-256 of its content.
+    #[arg(long)]
+    hashes: bool,
+
+    /// Extraction: annotate each Rust file with the modules it declares (`mod foo;`)
+    /// and the `crate::` paths it uses.
+    #[arg(long)]
+    module_map: bool,
+
+    /// Extraction: tag the heading of each executable file with `[executable]` (Unix only).
+    #[arg(long)]
+    note_exec: bool,
+
+    /// Rewrite line endings of generated files (lf or crlf); unchanged by default.
+    #[arg(long, value_enum, value_name = "EOL")]
+    normalize_eol: Option<EolCli>,
+
+    /// How parsed file content is trimmed: full, trailing or none (verbatim between fences).
+    #[arg(long, value_enum, value_name = "MODE", default_value = "full")]
+    trim_mode: TrimModeCli,
+
+    /// Derive each project's name from the file name, the first `# H1` heading, or the
+    /// `name` in its `Cargo.toml` block.
+    #[arg(long, value_enum, value_name = "SOURCE", default_value = "filename")]
+    name_from: NameFromCli,
+
+    /// Strip the leading indentation common to every line of each parsed block.
+    #[arg(long)]
+    dedent: bool,
+
+    /// What to do when a pattern yields several blocks for one path: `keep-first`
+    /// (default), `keep-last`, `concatenate` them, or fail with an `error`.
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "keep-first")]
+    on_duplicate: DuplicateCli,
+
+    /// Keep a code fence wrapping the whole content of a `<code path>` tag instead of
+    /// stripping it.
+    #[arg(long)]
+    no_strip_inner_fence: bool,
+
+    /// Collapse runs of blank lines in generated files into a single blank line (line-based).
+    #[arg(long)]
+    collapse_blank_lines: bool,
+
+    /// Skip rewriting generated files whose content is unchanged, preserving their mtime.
+    #[arg(long)]
+    touch_changed_only: bool,
+
+    /// Refuse to write files containing placeholder markers such as "rest of the code";
+    /// optionally takes a comma-separated list replacing the default markers.
+    #[arg(long, value_name = "MARKERS", num_args = 0.., value_delimiter = ',')]
+    reject_placeholders: Option<Vec<String>>,
+
+    /// Only generate files with these extensions (comma-separated, e.g. rs,toml); any
+    /// other parsed path fails the project before anything is written.
+    #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+    allow_ext: Vec<String>,
+
+    /// Hide per-file progress and print only a final tally of files, projects and errors.
+    #[arg(long)]
+    summary_only: bool,
+
+    /// Write the Markdown's prose (everything outside file blocks) to DESCRIPTION.md
+    /// in each generated project.
+    #[arg(long)]
+    keep_prose: bool,
+
+    /// Generate everything, but list lines that look like placeholders (`// ...`,
+    /// "rest of the code") in PLACEHOLDERS.md in each project.
+    #[arg(long)]
+    placeholder_report: bool,
+
+    /// Write the Markdown's first prose paragraph (before any file block) to README.md,
+    /// unless the blocks define one.
+    #[arg(long)]
+    auto_readme: bool,
+
+    /// Make files whose heading carries the `[executable]` tag (from `--note-exec`)
+    /// executable (Unix only).
+    #[arg(long)]
+    restore_exec: bool,
+
+    /// Remove this leading folder (e.g. `my_project`) from every parsed file path.
+    #[arg(long, value_name = "PREFIX")]
+    strip_path_prefix: Option<String>,
+
+    /// Write MANIFEST.sha256 (sha256sum format) listing each generated file's hash.
+    #[arg(long)]
+    checksum_manifest: bool,
+
+    /// Parse every Markdown file and print its block count without writing anything;
+    /// exits with code 3 if any file yields no blocks.
+    #[arg(long)]
+    parse_only: bool,
+
+    /// Print what each parser pattern matched in FILE (path, byte range, first line), then exit.
+    #[arg(long, value_name = "FILE")]
+    dump_parse_tree: Option<PathBuf>,
+
+    /// Print the pattern matching the most file blocks in FILE and the block count
+    /// (e.g. `hash 3`), then exit; exits with code 3 if no pattern matches.
+    #[arg(long, value_name = "FILE")]
+    detect_pattern: Option<PathBuf>,
+
+    /// Print the effective settings (after defaults and command-line flags) as JSON, then exit.
+    #[arg(long)]
+    dump_config: bool,
+
+    /// Run `cargo generate-lockfile` in each generated Rust project and stop ignoring
+    /// its Cargo.lock.
+    #[arg(long)]
+    lockfile: bool,
+
+    /// Run `cargo check` in each generated Rust project; a project that doesn't
+    /// compile is reported, and fails the run (exit code 5) under `--strict`.
+    #[arg(long)]
+    verify_compiles: bool,
+
+    /// Run `git init` in each generated project and commit the generated files.
+    #[arg(long)]
+    git_init: bool,
+
+    /// Parse each Markdown file in full and print how many file blocks it yields;
+    /// generate nothing.
+    #[arg(long)]
+    count_only: bool,
+
+    /// Buffer each Markdown file's log lines and print them as one block when it finishes.
+    #[arg(long)]
+    ordered_output: bool,
+
+    /// Limit the total size in bytes of Markdown files being processed at once.
+    #[arg(long, value_name = "BYTES")]
+    max_memory: Option<u64>,
+
+    /// Extraction: what to do with unreadable files: `fail` (default) or `skip` them with a note.
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "fail")]
+    on_read_error: ReadErrorCli,
+
+    /// Extraction: `skip` binary files (default) or embed them as `base64` with their size
+    /// and MIME type.
+    #[arg(long, value_enum, value_name = "MODE", default_value = "skip")]
+    binary: BinaryCli,
+
+    /// Extraction: write code fences with `backtick` (default) or `tilde` characters,
+    /// e.g. to nest the output inside another backtick-fenced block.
+    #[arg(long, value_enum, value_name = "CHAR", default_value = "backtick")]
+    fence_char: FenceCharCli,
+
+    /// Extraction: render the structure as a nested list linking each file to its section.
+    #[arg(long)]
+    linked_tree: bool,
+
+    /// Extraction: nest section headings by directory depth so the outline mirrors the tree.
+    #[arg(long)]
+    nested_headings: bool,
+
+    /// Extraction: include gitignored and hidden files (everything under the root
+    /// except `.git` and --skip entries).
+    #[arg(long)]
+    no_respect_gitignore: bool,
+
+    /// Extraction: strip trailing whitespace and blank-line runs from Rust and JS/TS files.
+    #[arg(long)]
+    minify: bool,
+
+    /// Extraction: with --minify, also drop `use` / `import` statements.
+    #[arg(long, requires = "minify")]
+    strip_imports: bool,
+
+    /// Extraction: fence language for files whose extension has no mapping (e.g. text).
+    #[arg(long, value_name = "LANG")]
+    default_lang: Option<String>,
+
+    /// Extraction: heading for each file section, with {path}, {lang}, {size}, {lines}
+    /// and {hash} placeholders, e.g. "{path} ({lang}, {lines} lines)".
+    #[arg(long, value_name = "TEMPLATE")]
+    header_template: Option<String>,
+
+    /// Extraction: truncate embedded lines longer than N characters, marking them `… [truncated]`.
+    #[arg(long, value_name = "N")]
+    max_line_length: Option<usize>,
+
+    /// Extraction: strip trailing whitespace from each embedded line.
+    #[arg(long = "trim-trailing-ws")]
+    trim_trailing_ws: bool,
+
+    /// Extraction: write one Markdown document (codebase.md), one JSON object per
+    /// file (codebase.ndjson) or one AsciiDoc document (codebase.adoc).
+    #[arg(long, value_enum, default_value = "markdown")]
+    format: FormatCli,
+
+    /// Extraction: emit only the tree and a list of file paths with sizes, reading no files.
+    #[arg(long)]
+    manifest_only: bool,
+
+    /// Extraction: instead of file contents, report whether each file uses LF, CRLF or
+    /// mixed line endings, with a summary.
+    #[arg(long)]
+    line_ending_report: bool,
+
+    /// Extraction: only include files tracked by git (requires a git repository).
+    #[arg(long)]
+    tracked_only: bool,
+
+    /// Extraction: embed exactly the files listed in FILE (one relative path per line),
+    /// in that order, instead of walking the directory.
+    #[arg(long, value_name = "FILE")]
+    from_list: Option<PathBuf>,
+
+    /// Extraction: only include files modified within this duration (e.g. 24h, 7d),
+    /// going by filesystem mtime.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    since_mtime: Option<Duration>,
+
+    /// Extraction: rename fence languages, e.g. `rust=rs,bash=shell`.
+    #[arg(long, value_name = "FROM=TO", value_delimiter = ',', value_parser = parse_alias)]
+    fence_alias: Vec<(String, String)>,
+
+    /// Extraction: replace substrings in displayed paths (tree, headings), e.g.
+    /// acme=company; files are still read from their real paths.
+    #[arg(long, value_name = "FROM=TO", value_delimiter = ',', value_parser = parse_alias)]
+    anonymize: Vec<(String, String)>,
+
+    /// Extraction: apply the --anonymize replacements to file contents too.
+    #[arg(long, requires = "anonymize")]
+    anonymize_content: bool,
+
+    /// Extraction: reuse rendered sections of unchanged files from this cache directory.
+    #[arg(long, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Treat each top-level `# Name` section of a Markdown file as its own project.
+    #[arg(long)]
+    split_on_h1: bool,
+
+    /// Treat a Markdown file that yields no file blocks as a failure (exit code 3).
+    #[arg(long)]
+    strict: bool,
+
+    /// When no pattern matches, write the first fenced block (or the whole Markdown) to this path.
+    #[arg(long, value_name = "PATH")]
+    fallback_single: Option<String>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
 enum CommandChoice {
     Sample,
     Prompt,
     Extract,
     Tree,
+    Convert,
     None,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
 enum MdPatternCli {
     CodeTag,
     Hash,
@@ -60,6 +410,191 @@ enum MdPatternCli {
     Raw,
     FileCode,
     FileFence,
+    FencedInfoPath,
+}
+
+/// Parses a duration such as `90s`, `15m`, `24h`, `7d` or `2w`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected a number followed by s, m, h, d or w, got {:?}", s))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown duration unit {:?} in {:?}", unit, s)),
+    };
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {:?} is too large", s))
+}
+
+/// Parses a `from=to` alias pair.
+fn parse_alias(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        }
+        _ => Err(format!("expected FROM=TO, got {:?}", s)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum EolCli {
+    Lf,
+    Crlf,
+}
+
+impl From<EolCli> for file_gen::LineEnding {
+    fn from(item: EolCli) -> Self {
+        match item {
+            EolCli::Lf => file_gen::LineEnding::Lf,
+            EolCli::Crlf => file_gen::LineEnding::CrLf,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum ReadErrorCli {
+    Skip,
+    Fail,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum FenceCharCli {
+    Backtick,
+    Tilde,
+}
+
+impl From<FenceCharCli> for extract::FenceChar {
+    fn from(item: FenceCharCli) -> Self {
+        match item {
+            FenceCharCli::Backtick => extract::FenceChar::Backtick,
+            FenceCharCli::Tilde => extract::FenceChar::Tilde,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum BinaryCli {
+    Skip,
+    Base64,
+}
+
+impl From<BinaryCli> for extract::BinaryPolicy {
+    fn from(item: BinaryCli) -> Self {
+        match item {
+            BinaryCli::Skip => extract::BinaryPolicy::Skip,
+            BinaryCli::Base64 => extract::BinaryPolicy::Base64,
+        }
+    }
+}
+
+impl From<ReadErrorCli> for extract::ReadErrorPolicy {
+    fn from(item: ReadErrorCli) -> Self {
+        match item {
+            ReadErrorCli::Skip => extract::ReadErrorPolicy::Skip,
+            ReadErrorCli::Fail => extract::ReadErrorPolicy::Fail,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum TrimModeCli {
+    Full,
+    Trailing,
+    None,
+}
+
+impl From<TrimModeCli> for parser::TrimMode {
+    fn from(item: TrimModeCli) -> Self {
+        match item {
+            TrimModeCli::Full => parser::TrimMode::Full,
+            TrimModeCli::Trailing => parser::TrimMode::Trailing,
+            TrimModeCli::None => parser::TrimMode::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum FormatCli {
+    Markdown,
+    Ndjson,
+    Asciidoc,
+}
+
+impl From<FormatCli> for extract::ExtractFormat {
+    fn from(item: FormatCli) -> Self {
+        match item {
+            FormatCli::Markdown => extract::ExtractFormat::Markdown,
+            FormatCli::Ndjson => extract::ExtractFormat::Ndjson,
+            FormatCli::Asciidoc => extract::ExtractFormat::AsciiDoc,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum DuplicateCli {
+    KeepFirst,
+    KeepLast,
+    Concatenate,
+    Error,
+}
+
+impl From<DuplicateCli> for parser::DuplicatePolicy {
+    fn from(item: DuplicateCli) -> Self {
+        match item {
+            DuplicateCli::KeepFirst => parser::DuplicatePolicy::KeepFirst,
+            DuplicateCli::KeepLast => parser::DuplicatePolicy::KeepLast,
+            DuplicateCli::Concatenate => parser::DuplicatePolicy::Concatenate,
+            DuplicateCli::Error => parser::DuplicatePolicy::Error,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum NameFromCli {
+    Filename,
+    H1,
+    Cargo,
+}
+
+impl From<NameFromCli> for scanner::NameSource {
+    fn from(item: NameFromCli) -> Self {
+        match item {
+            NameFromCli::Filename => scanner::NameSource::Filename,
+            NameFromCli::H1 => scanner::NameSource::H1,
+            NameFromCli::Cargo => scanner::NameSource::Cargo,
+        }
+    }
+}
+
+impl From<parser::MdPatternType> for MdPatternCli {
+    fn from(item: parser::MdPatternType) -> Self {
+        match item {
+            parser::MdPatternType::CodeTag => MdPatternCli::CodeTag,
+            parser::MdPatternType::HashMarker => MdPatternCli::Hash,
+            parser::MdPatternType::Delimiter => MdPatternCli::Delimiter,
+            parser::MdPatternType::Raw => MdPatternCli::Raw,
+            parser::MdPatternType::FileCode => MdPatternCli::FileCode,
+            parser::MdPatternType::FileFence => MdPatternCli::FileFence,
+            parser::MdPatternType::FencedInfoPath => MdPatternCli::FencedInfoPath,
+        }
+    }
 }
 
 impl From<MdPatternCli> for parser::MdPatternType {
@@ -71,110 +606,741 @@ impl From<MdPatternCli> for parser::MdPatternType {
             MdPatternCli::Raw => parser::MdPatternType::Raw,
             MdPatternCli::FileCode => parser::MdPatternType::FileCode,
             MdPatternCli::FileFence => parser::MdPatternType::FileFence,
+            MdPatternCli::FencedInfoPath => parser::MdPatternType::FencedInfoPath,
         }
     }
 }
 
+/// Process exit codes, so scripts can tell failure modes apart.
+///
+/// When several Markdown files fail differently, the highest code wins.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum ExitCode {
+    Success = 0,
+    NoInput = 2,
+    ParseFailure = 3,
+    GenerationFailed = 4,
+    ExecutionFailed = 5,
+}
+
+impl ExitCode {
+    fn exit(self) -> ! {
+        process::exit(self as i32)
+    }
+}
+
+/// Sends log records to stderr, at `default_level` unless `RUST_LOG` says otherwise.
+fn init_logger(default_level: &str) {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level))
+        .format(|buf, record| match record.level() {
+            log::Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level, record.args()),
+        })
+        .init();
+}
+
 fn main() {
     let cli = Cli::parse();
+    init_logger(if cli.summary_only { "warn" } else { "info" });
+
+    if cli.dump_config {
+        dump_config(&cli);
+        return;
+    }
+    if let Some(path) = &cli.dump_parse_tree {
+        dump_parse_tree(&cli, path);
+        return;
+    }
+    if let Some(path) = &cli.detect_pattern {
+        detect_pattern(&cli, path);
+        return;
+    }
 
     // Handle sample, prompt, and extract subcommands.
     match cli.command {
         CommandChoice::Sample => {
-            if let Err(e) = extra::generate_sample_md() {
-                eprintln!("Error generating sample.md: {}", e);
-                process::exit(1);
+            if let Err(e) = extra::generate_sample_md(cli.wrap_width) {
+                log::error!("Error generating sample.md: {}", e);
+                ExitCode::GenerationFailed.exit();
             }
             return;
         }
         CommandChoice::Prompt => {
-            if let Err(e) = extra::generate_prompt_md() {
-                eprintln!("Error generating prompt.md: {}", e);
-                process::exit(1);
+            if let Err(e) = extra::generate_prompt_md(cli.wrap_width) {
+                log::error!("Error generating prompt.md: {}", e);
+                ExitCode::GenerationFailed.exit();
             }
             return;
         }
         CommandChoice::Extract => {
             let current_dir = env::current_dir().expect("Failed to get current directory");
-            let ignore_file = current_dir.join(".gitignore");
             let config = ExtractConfig {
                 root: current_dir.clone(),
-                ignore_file: if ignore_file.exists() {
-                    Some(ignore_file)
-                } else {
-                    None
-                },
+                ignore_file: cli.ignore_file.clone(),
                 extra_ignores: cli.skip.clone(),
+                only: cli.only.clone(),
                 project_type: cli.project_type.clone(),
-                pattern: cli.pattern.clone(),
+                pattern: cli.pattern.first().copied(),
+                inline_readmes: cli.inline_readmes,
+                min_file_size: cli.min_file_size,
+                max_file_size: cli.max_file_size,
+                content_filter: cli.content_filter.clone(),
+                context_lines: cli.context_lines,
+                language_only: cli.language_only.clone(),
+                hashes: cli.hashes,
+                module_map: cli.module_map,
+                note_exec: cli.note_exec,
+                fence_aliases: cli.fence_alias.iter().cloned().collect(),
+                anonymize: cli.anonymize.clone(),
+                anonymize_content: cli.anonymize_content,
+                tracked_only: cli.tracked_only,
+                modified_within: cli.since_mtime,
+                from_list: cli.from_list.clone(),
+                manifest_only: cli.manifest_only,
+                line_ending_report: cli.line_ending_report,
+                linked_tree: cli.linked_tree,
+                on_read_error: cli.on_read_error.into(),
+                binary: cli.binary.into(),
+                fence_char: cli.fence_char.into(),
+                cache_dir: cli.cache.clone(),
+                nested_headings: cli.nested_headings,
+                include_ignored: cli.no_respect_gitignore,
+                minify: cli.minify,
+                strip_imports: cli.strip_imports,
+                trim_trailing_ws: cli.trim_trailing_ws,
+                default_lang: cli.default_lang.clone(),
+                max_line_length: cli.max_line_length,
+                header_template: cli.header_template.clone(),
+                format: cli.format.into(),
             };
-            match extract_to_markdown(config) {
-                Ok(md) => {
-                    let out_md = Path::new(&cli.output_dir).join("codebase.md");
-                    fs::create_dir_all(&cli.output_dir).unwrap();
-                    fs::write(&out_md, md).expect("Failed to write codebase.md");
-                    println!("Extracted markdown to {:?}", out_md);
+            let out_name = match cli.format {
+                FormatCli::Markdown => "codebase.md",
+                FormatCli::Ndjson => "codebase.ndjson",
+                FormatCli::Asciidoc => "codebase.adoc",
+            };
+            let out_md = Path::new(&cli.output_dir).join(out_name);
+            let result = fs::create_dir_all(&cli.output_dir)
+                .and_then(|_| fs::File::create(&out_md))
+                .map_err(anyhow::Error::from)
+                .and_then(|file| extract_to_writer(config, BufWriter::new(file)));
+            match result {
+                Ok(()) => log::info!("Extracted markdown to {:?}", out_md),
+                Err(e) => {
+                    log::error!("Extraction failed: {}", e);
+                    ExitCode::GenerationFailed.exit();
                 }
+            }
+            return;
+        }
+        CommandChoice::Tree => {
+            let out_md = Path::new(&cli.output_dir).join("tree.md");
+            let result = extract::generate_tree_markdown().and_then(|md| {
+                fs::create_dir_all(&cli.output_dir)?;
+                fs::write(&out_md, md)?;
+                Ok(())
+            });
+            match result {
+                Ok(()) => log::info!("Generated project tree in {:?}", out_md),
                 Err(e) => {
-                    eprintln!("Extraction failed: {}", e);
-                    process::exit(1);
+                    log::error!("Tree generation failed: {}", e);
+                    ExitCode::GenerationFailed.exit();
                 }
             }
             return;
         }
-        CommandChoice::Tree => match extract::generate_tree_markdown() {
-            Ok(md) => {
-                let out_md = Path::new(&cli.output_dir).join("tree.md");
-                fs::create_dir_all(&cli.output_dir).unwrap();
-                fs::write(&out_md, md.clone()).expect("Failed to write tree.md");
-                println!("Generated project tree in {:?}", out_md);
-                return;
+        CommandChoice::Convert => {
+            let current_dir = env::current_dir().expect("Failed to get current directory");
+            let md_files = scanner::find_md_files_with_extensions(&current_dir, &cli.md_ext);
+            if md_files.is_empty() {
+                log::error!(
+                    "No Markdown files (.{}) found in the current directory.",
+                    cli.md_ext.join(", .")
+                );
+                ExitCode::NoInput.exit();
             }
-            Err(e) => {
-                eprintln!("Tree generation failed: {}", e);
-                process::exit(1);
+            let code = md_files
+                .iter()
+                .map(|path| convert_markdown_file(&cli, path))
+                .max()
+                .unwrap_or(ExitCode::Success);
+            if code != ExitCode::Success {
+                code.exit();
             }
-        },
+            return;
+        }
         CommandChoice::None => {}
     }
 
     // Default: generate Rust projects from Markdown files.
     let current_dir = env::current_dir().expect("Failed to get current directory");
-    println!("Scanning folder: {:?}", current_dir);
+    log::info!("Scanning folder: {:?}", current_dir);
 
-    let md_files = scanner::find_md_files(&current_dir);
+    let md_files = scanner::find_md_files_with_extensions(&current_dir, &cli.md_ext);
     if md_files.is_empty() {
-        eprintln!("No .md files found in the current directory.");
-        process::exit(1);
-    }
-
-    md_files.par_iter().for_each(|file_path| {
-        println!("Processing file: {:?}", file_path);
-        match scanner::read_file(file_path) {
-            Ok(content) => {
-                let forced = cli.pattern.map(|pt| pt.into());
-                let parsed_files = parser::parse_content(&content, forced);
-                if parsed_files.is_empty() {
-                    println!("No valid file blocks found in {:?}", file_path);
-                } else if let Some(project_name) = scanner::extract_project_name(file_path) {
-                    let output_dir = format!("{}/{}", cli.output_dir, project_name);
-                    if let Err(err) =
-                        file_gen::generate_project_with_dir(&output_dir, parsed_files, file_path)
-                    {
-                        eprintln!("Error generating project {}: {}", project_name, err);
-                    } else {
-                        println!("Project {} generated in {}", project_name, output_dir);
-                        if cli.execute {
-                            let project_path = Path::new(&output_dir);
-                            if let Err(err) = execute_project_if_needed(project_path, project_path)
-                            {
-                                eprintln!("Execution failed for {}: {}", project_name, err);
-                            }
-                        }
-                    }
+        log::error!(
+            "No Markdown files (.{}) found in the current directory.",
+            cli.md_ext.join(", .")
+        );
+        ExitCode::NoInput.exit();
+    }
+
+    if cli.count_only {
+        count_only(&cli, &md_files);
+        return;
+    }
+
+    let exec_pool = ExecPool::new(cli.exec_jobs);
+    let summary = Summary::default();
+    let code = if let Some(name) = &cli.merge_into {
+        let code = merge_markdown(&cli, name, &md_files, &exec_pool, &summary);
+        if code != ExitCode::Success {
+            summary.errors.fetch_add(1, Ordering::SeqCst);
+        }
+        code
+    } else {
+        let memory = cli
+            .max_memory
+            .map(|bytes| Semaphore::new(usize::try_from(bytes).unwrap_or(usize::MAX)));
+        let cancelled = AtomicBool::new(false);
+        md_files
+            .par_iter()
+            .map(|file_path| {
+                if cli.fail_fast && cancelled.load(Ordering::SeqCst) {
+                    return ExitCode::Success;
                 }
-            }
-            Err(e) => eprintln!("Error reading file {:?}: {}", file_path, e),
+                let code = process_markdown(&cli, file_path, &exec_pool, memory.as_ref(), &summary);
+                if code != ExitCode::Success {
+                    cancelled.store(true, Ordering::SeqCst);
+                    summary.errors.fetch_add(1, Ordering::SeqCst);
+                }
+                code
+            })
+            .max()
+            .unwrap_or(ExitCode::Success)
+    };
+    if cli.summary_only {
+        println!(
+            "{} Markdown files, {} projects generated, {} files written, {} errors",
+            md_files.len(),
+            summary.projects.load(Ordering::SeqCst),
+            summary.files.load(Ordering::SeqCst),
+            summary.errors.load(Ordering::SeqCst)
+        );
+    }
+    if code != ExitCode::Success {
+        code.exit();
+    }
+}
+
+/// Tallies of a generation run, reported by `--summary-only`.
+#[derive(Default)]
+struct Summary {
+    projects: AtomicUsize,
+    files: AtomicUsize,
+    /// Markdown files whose processing failed.
+    errors: AtomicUsize,
+}
+
+/// Rewrites one Markdown file from the `--from` pattern to the `--to` pattern, writing
+/// the result under the output directory with the same file name.
+fn convert_markdown_file(cli: &Cli, path: &Path) -> ExitCode {
+    let content = match scanner::read_file(path) {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Error reading file {:?}: {}", path, e);
+            return ExitCode::ParseFailure;
+        }
+    };
+    let converted = extract::convert_markdown(&content, cli.from, cli.to);
+    let out_md = Path::new(&cli.output_dir).join(path.file_name().unwrap_or_default());
+    match fs::create_dir_all(&cli.output_dir).and_then(|_| fs::write(&out_md, converted)) {
+        Ok(()) => {
+            log::info!("Converted {:?} to {:?}", path, out_md);
+            ExitCode::Success
+        }
+        Err(e) => {
+            log::error!("Failed to write {:?}: {}", out_md, e);
+            ExitCode::GenerationFailed
+        }
+    }
+}
+
+/// Prints how many file blocks each Markdown file yields, and the total, without generating.
+fn count_only(cli: &Cli, md_files: &[PathBuf]) {
+    let config = parse_config(cli);
+    let counts: Vec<(&PathBuf, usize)> = md_files
+        .par_iter()
+        .map(|path| {
+            let count = scanner::read_file(path)
+                .map(|content| parser::count_blocks(&content, &config))
+                .unwrap_or_else(|e| {
+                    log::error!("Error reading file {:?}: {}", path, e);
+                    0
+                });
+            (path, count)
+        })
+        .collect();
+    for (path, count) in &counts {
+        println!("{}: {} blocks", path.display(), count);
+    }
+    let total: usize = counts.iter().map(|(_, count)| count).sum();
+    println!("Total: {} blocks", total);
+}
+
+/// Prints what each sub-parser matched in `path`, grouped by pattern, without generating.
+fn dump_parse_tree(cli: &Cli, path: &Path) {
+    let content = match scanner::read_file(path) {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Error reading file {:?}: {}", path, e);
+            ExitCode::ParseFailure.exit();
+        }
+    };
+    let matches = parser::dump_parse_tree(&content, &parse_config(cli));
+    let mut current = None;
+    for m in &matches {
+        if current != Some(m.pattern) {
+            println!("{:?}:", m.pattern);
+            current = Some(m.pattern);
         }
+        println!(
+            "  {}  bytes {}..{}  {}",
+            m.path, m.span.start, m.span.end, m.first_line
+        );
+    }
+    if matches.is_empty() {
+        println!("No pattern matched.");
+    }
+}
+
+/// Prints the best-matching pattern for `path` under its CLI name, with its block count.
+fn detect_pattern(cli: &Cli, path: &Path) {
+    let content = match scanner::read_file(path) {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Error reading file {:?}: {}", path, e);
+            ExitCode::ParseFailure.exit();
+        }
+    };
+    let Some((pattern, count)) = parser::detect_best_pattern(&content, &parse_config(cli)) else {
+        log::error!("No pattern matched {:?}", path);
+        ExitCode::ParseFailure.exit();
+    };
+    println!("{} {}", value_name(MdPatternCli::from(pattern)), count);
+}
+
+/// The command-line spelling of a `ValueEnum` variant, e.g. `file-fence`.
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Prints the settings a run would use, so unexpected behaviour can be traced to a flag.
+fn dump_config(cli: &Cli) {
+    match serde_json::to_string_pretty(cli) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            log::error!("Error serializing configuration: {}", e);
+            ExitCode::GenerationFailed.exit();
+        }
+    }
+}
+
+/// Serializes a regex option as its pattern.
+fn serialize_regex<S: serde::Serializer>(regex: &Option<Regex>, s: S) -> Result<S::Ok, S::Error> {
+    match regex {
+        Some(regex) => s.serialize_some(regex.as_str()),
+        None => s.serialize_none(),
+    }
+}
+
+/// Parsing options selected on the command line.
+fn parse_config(cli: &Cli) -> parser::ParseConfig {
+    parser::ParseConfig {
+        forced: cli.pattern.iter().map(|&pt| pt.into()).collect(),
+        merge_fences_under_heading: cli.merge_fences_under_heading,
+        trim_mode: cli.trim_mode.into(),
+        dedent: cli.dedent,
+        keep_inner_fence: cli.no_strip_inner_fence,
+        per_block: cli.pattern_from_content,
+        ..Default::default()
+    }
+}
+
+/// Parses one Markdown file and generates (and optionally executes) its project(s).
+///
+/// With a `memory` budget, the file's size is reserved from it before reading and
+/// held until its projects are generated, bounding the content resident across
+/// tasks; the post-generation steps (`--verify-compiles`, `--execute`, ...) run
+/// after the reservation is released.
+fn process_markdown(
+    cli: &Cli,
+    file_path: &Path,
+    exec_pool: &ExecPool,
+    memory: Option<&Semaphore>,
+    summary: &Summary,
+) -> ExitCode {
+    let log = TaskLog::new(cli.ordered_output);
+    log.info(format!("Processing file: {:?}", file_path));
+    let reservation = memory.map(|budget| {
+        let size = fs::metadata(file_path).map(|m| m.len()).unwrap_or(0);
+        let permit = budget.acquire_many(usize::try_from(size).unwrap_or(usize::MAX));
+        log::debug!(
+            "Reserved {} bytes of --max-memory for {:?}",
+            size,
+            file_path
+        );
+        permit
     });
+    let content = match scanner::read_file(file_path) {
+        Ok(content) => content,
+        Err(e) => {
+            log.error(format!("Error reading file {:?}: {}", file_path, e));
+            return ExitCode::ParseFailure;
+        }
+    };
+
+    let mut projects = if cli.split_on_h1 {
+        parser::split_on_h1(&content)
+    } else {
+        Vec::new()
+    };
+    if projects.is_empty() {
+        let Some(project_name) = scanner::project_name(
+            file_path,
+            &content,
+            cli.name_from.into(),
+            &parse_config(cli),
+        ) else {
+            return ExitCode::Success;
+        };
+        projects.push((project_name, content));
+    }
+    if cli.sanitize_names {
+        for (name, _) in &mut projects {
+            *name = scanner::sanitize_project_name(name);
+        }
+    }
+    // An H1 section name becomes a folder under the output directory, so it must
+    // not nest folders or climb out of it.
+    if let Some((name, _)) = projects
+        .iter()
+        .find(|(name, _)| !scanner::is_safe_project_name(name))
+    {
+        log.error(format!(
+            "Refusing to generate {:?}: section name {:?} is not a single folder name",
+            file_path, name
+        ));
+        return ExitCode::ParseFailure;
+    }
+
+    // Parse every section first: the block cap applies to the whole document, so
+    // nothing is generated when the sections add up to more than it.
+    let parsed: Vec<Result<Vec<parser::ParsedFile>, ExitCode>> = projects
+        .iter()
+        .map(|(_, section)| parse_section(cli, file_path, section, &log))
+        .collect();
+    let count: usize = parsed.iter().flatten().map(Vec::len).sum();
+    if let Some(max) = cli.max_blocks
+        && count > max
+    {
+        let err = parser::TooManyBlocksError { count, max };
+        log.error(format!("Refusing to generate {:?}: {}", file_path, err));
+        return ExitCode::ParseFailure;
+    }
+
+    let generated: Vec<Result<Option<String>, ExitCode>> = projects
+        .iter()
+        .zip(parsed)
+        .map(|((project_name, section), parsed_files)| {
+            generate_section(
+                cli,
+                file_path,
+                project_name,
+                section,
+                parsed_files?,
+                &log,
+                summary,
+            )
+        })
+        .collect();
+
+    // Only the post-generation steps are left, and they do not need the content.
+    let names: Vec<String> = projects.into_iter().map(|(name, _)| name).collect();
+    if let Some(reservation) = reservation {
+        log::debug!("Released the --max-memory reservation for {:?}", file_path);
+        drop(reservation);
+    }
+    names
+        .iter()
+        .zip(generated)
+        .map(|(project_name, generated)| match generated {
+            Ok(Some(output_dir)) => finish_project(cli, project_name, &output_dir, exec_pool, &log),
+            Ok(None) => ExitCode::Success,
+            Err(code) => code,
+        })
+        .max()
+        .unwrap_or(ExitCode::Success)
+}
+
+/// Parses a Markdown section, falling back to `--fallback-single` when it has no
+/// file blocks.
+fn parse_section(
+    cli: &Cli,
+    file_path: &Path,
+    content: &str,
+    log: &TaskLog,
+) -> Result<Vec<parser::ParsedFile>, ExitCode> {
+    let parse_config = parse_config(cli);
+    let mut parsed_files =
+        match parser::parse_content_with_policy(content, &parse_config, cli.on_duplicate.into()) {
+            Ok(files) => files,
+            Err(err) => {
+                log.error(format!("Error parsing {:?}: {}", file_path, err));
+                return Err(ExitCode::ParseFailure);
+            }
+        };
+    if parsed_files.is_empty()
+        && let Some(path) = &cli.fallback_single
+    {
+        parsed_files.extend(parser::parse_fallback_single(content, path));
+    }
+    Ok(parsed_files)
+}
+
+/// Generates the parsed blocks of a Markdown section as the project `project_name`,
+/// returning its output directory as [`generate_parsed`] does.
+fn generate_section(
+    cli: &Cli,
+    file_path: &Path,
+    project_name: &str,
+    content: &str,
+    parsed_files: Vec<parser::ParsedFile>,
+    log: &TaskLog,
+    summary: &Summary,
+) -> Result<Option<String>, ExitCode> {
+    if cli.parse_only {
+        if cli.split_on_h1 {
+            println!(
+                "{} ({}): {} blocks",
+                file_path.display(),
+                project_name,
+                parsed_files.len()
+            );
+        } else {
+            println!("{}: {} blocks", file_path.display(), parsed_files.len());
+        }
+        if parsed_files.is_empty() {
+            log.error(format!("No file blocks found in {:?}", file_path));
+            return Err(ExitCode::ParseFailure);
+        }
+        return Ok(None);
+    }
+    generate_parsed(
+        cli,
+        &[file_path],
+        project_name,
+        content,
+        parsed_files,
+        log,
+        summary,
+    )
+}
+
+/// Parses every Markdown file and generates the union of their file blocks as the
+/// single project `project_name`, resolving paths defined by several files with
+/// `--on-duplicate`.
+fn merge_markdown(
+    cli: &Cli,
+    project_name: &str,
+    md_files: &[PathBuf],
+    exec_pool: &ExecPool,
+    summary: &Summary,
+) -> ExitCode {
+    let log = TaskLog::new(cli.ordered_output);
+    let parse_config = parse_config(cli);
+    let mut contents = Vec::new();
+    let mut parsed_files = Vec::new();
+    for file_path in md_files {
+        log.info(format!("Processing file: {:?}", file_path));
+        let content = match scanner::read_file(file_path) {
+            Ok(content) => content,
+            Err(e) => {
+                log.error(format!("Error reading file {:?}: {}", file_path, e));
+                return ExitCode::ParseFailure;
+            }
+        };
+        let files = match parser::parse_content_with_policy(
+            &content,
+            &parse_config,
+            cli.on_duplicate.into(),
+        ) {
+            Ok(files) => files,
+            Err(err) => {
+                log.error(format!("Error parsing {:?}: {}", file_path, err));
+                return ExitCode::ParseFailure;
+            }
+        };
+        if let Some(max) = cli.max_blocks
+            && let Err(err) = parser::check_block_limit(&files, max)
+        {
+            log.error(format!("Refusing to generate {:?}: {}", file_path, err));
+            return ExitCode::ParseFailure;
+        }
+        parsed_files.extend(files);
+        contents.push(content);
+    }
+    let parsed_files = match parser::resolve_duplicates(parsed_files, cli.on_duplicate.into()) {
+        Ok(files) => files,
+        Err(err) => {
+            log.error(format!("Error merging into {}: {}", project_name, err));
+            return ExitCode::ParseFailure;
+        }
+    };
+    let sources: Vec<&Path> = md_files.iter().map(PathBuf::as_path).collect();
+    match generate_parsed(
+        cli,
+        &sources,
+        project_name,
+        &contents.join("\n\n"),
+        parsed_files,
+        &log,
+        summary,
+    ) {
+        Ok(Some(output_dir)) => finish_project(cli, project_name, &output_dir, exec_pool, &log),
+        Ok(None) => ExitCode::Success,
+        Err(code) => code,
+    }
+}
+
+/// Generates `parsed_files`, parsed from the Markdown `content` of `sources`, as the
+/// project `project_name`. Returns the output directory to hand to
+/// [`finish_project`], or `None` when there was nothing to generate.
+fn generate_parsed(
+    cli: &Cli,
+    sources: &[&Path],
+    project_name: &str,
+    content: &str,
+    parsed_files: Vec<parser::ParsedFile>,
+    log: &TaskLog,
+    summary: &Summary,
+) -> Result<Option<String>, ExitCode> {
+    let parse_config = parse_config(cli);
+    if parsed_files.is_empty() {
+        log.info(format!(
+            "No valid file blocks found for {} in {:?}",
+            project_name, sources
+        ));
+        return if cli.strict {
+            Err(ExitCode::ParseFailure)
+        } else {
+            Ok(None)
+        };
+    }
+
+    let output_dir = format!("{}/{}", cli.output_dir, project_name);
+    let gen_config = file_gen::GenerateConfig {
+        normalize_eol: cli.normalize_eol.map(|eol| eol.into()),
+        touch_changed_only: cli.touch_changed_only,
+        collapse_blank_lines: cli.collapse_blank_lines,
+        placeholder_markers: match &cli.reject_placeholders {
+            Some(markers) if markers.is_empty() => file_gen::DEFAULT_PLACEHOLDER_MARKERS
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            Some(markers) => markers.clone(),
+            None => Vec::new(),
+        },
+        allowed_extensions: cli.allow_ext.clone(),
+        checksum_manifest: cli.checksum_manifest,
+        placeholder_report: cli.placeholder_report,
+        strip_path_prefix: cli.strip_path_prefix.clone(),
+        readme: if cli.auto_readme {
+            parser::leading_paragraph(content, &parse_config)
+        } else {
+            None
+        },
+        restore_exec: cli.restore_exec,
+    };
+    match file_gen::generate_project_with_sources(&output_dir, parsed_files, sources, &gen_config) {
+        Ok(written) => {
+            summary.projects.fetch_add(1, Ordering::SeqCst);
+            summary.files.fetch_add(written, Ordering::SeqCst);
+        }
+        Err(err) => {
+            log.error(format!(
+                "Error generating project {}: {}",
+                project_name, err
+            ));
+            return Err(ExitCode::GenerationFailed);
+        }
+    }
+    if cli.keep_prose {
+        let prose = parser::residual_prose(content, &parse_config);
+        if !prose.is_empty() {
+            let description = Path::new(&output_dir).join("DESCRIPTION.md");
+            if let Err(err) = fs::write(&description, prose + "\n") {
+                log.error(format!("Error writing {:?}: {}", description, err));
+                return Err(ExitCode::GenerationFailed);
+            }
+        }
+    }
+    log.info(format!(
+        "Project {} generated in {}",
+        project_name, output_dir
+    ));
+    Ok(Some(output_dir))
+}
+
+/// Runs the post-generation steps (lockfile, compile check, `git init`, execution)
+/// for the project `project_name` generated in `output_dir`.
+fn finish_project(
+    cli: &Cli,
+    project_name: &str,
+    output_dir: &str,
+    exec_pool: &ExecPool,
+    log: &TaskLog,
+) -> ExitCode {
+    if cli.lockfile {
+        execute::generate_lockfile(Path::new(&output_dir), &SystemRunner, log);
+    }
+    // Checked before `--git-init` so the commit includes `check_output.log`.
+    if cli.verify_compiles {
+        match exec_pool.verify_compiles(Path::new(&output_dir), &SystemRunner, log) {
+            Ok(true) => {}
+            Ok(false) if cli.strict => return ExitCode::ExecutionFailed,
+            Ok(false) => {}
+            Err(err) => {
+                log.error(format!(
+                    "Compile check failed for {}: {}",
+                    project_name, err
+                ));
+                return ExitCode::ExecutionFailed;
+            }
+        }
+    }
+    if cli.git_init {
+        execute::git_init_project(Path::new(&output_dir), &SystemRunner, log);
+    }
+
+    if cli.execute {
+        let project_path = Path::new(&output_dir);
+        match exec_pool.execute(project_path, project_path, &SystemRunner, log) {
+            Ok(true) => {}
+            Ok(false) => {
+                log.error(format!(
+                    "Execution failed for {}; see its output logs",
+                    project_name
+                ));
+                return ExitCode::ExecutionFailed;
+            }
+            Err(err) => {
+                log.error(format!("Execution failed for {}: {}", project_name, err));
+                return ExitCode::ExecutionFailed;
+            }
+        }
+    }
+    ExitCode::Success
 }
@@ -0,0 +1,212 @@
+//! Conservative whitespace minification of source files embedded by `extract`.
+//!
+//! Only layout that cannot change meaning is touched: trailing whitespace, runs of
+//! blank lines and (optionally) import statements. Lines that end inside a string
+//! literal or block comment are kept verbatim, so multi-line literals survive intact.
+
+/// Languages the minifier understands; anything else is embedded unchanged.
+pub fn supports(lang: &str) -> bool {
+    matches!(lang, "rust" | "javascript" | "typescript")
+}
+
+/// Minifies `content` written in `lang` (a fence language such as "rust").
+///
+/// With `strip_imports`, private `use` declarations (Rust) or `import` statements
+/// (JS/TS) are dropped as well; the result is then for reading, not compiling.
+pub fn minify(lang: &str, content: &str, strip_imports: bool) -> String {
+    if !supports(lang) {
+        return content.to_string();
+    }
+    let mut lexer = Lexer::new(lang == "rust");
+    let mut out: Vec<&str> = Vec::new();
+    let mut in_import = false;
+    for line in content.lines() {
+        // Whether this line starts inside a literal/comment carried over from above.
+        let continued = lexer.state != State::Code;
+        lexer.scan_line(line);
+        let open_at_end = lexer.state != State::Code;
+
+        let line = if open_at_end { line } else { line.trim_end() };
+        if continued {
+            out.push(line);
+            continue;
+        }
+        if strip_imports && (in_import || is_import_start(lang, line)) {
+            in_import = !ends_import(lang, line);
+            continue;
+        }
+        if line.is_empty() && out.last().is_none_or(|prev| prev.is_empty()) {
+            continue;
+        }
+        out.push(line);
+    }
+    while out.last().is_some_and(|line| line.is_empty()) {
+        out.pop();
+    }
+    let mut text = out.join("\n");
+    text.push('\n');
+    text
+}
+
+fn is_import_start(lang: &str, line: &str) -> bool {
+    let line = line.trim_start();
+    if lang == "rust" {
+        line.starts_with("use ")
+    } else {
+        line.starts_with("import ") || line.starts_with("import{")
+    }
+}
+
+/// Whether an import statement is complete at the end of `line`.
+fn ends_import(lang: &str, line: &str) -> bool {
+    let line = line.trim_end();
+    if lang == "rust" {
+        return line.ends_with(';');
+    }
+    line.ends_with(';')
+        || line.ends_with('\'')
+        || line.ends_with('"')
+        || (!line.contains('{') || line.contains('}')) && line.contains(" from ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Code,
+    /// Inside a quoted string; the delimiter is `"`, `'` or `` ` ``.
+    Str(char),
+    /// Inside a Rust raw string closed by `"` followed by this many `#`.
+    RawStr(usize),
+    /// Inside a (possibly nested, for Rust) block comment.
+    BlockComment(usize),
+}
+
+/// Tracks just enough lexical state to know whether a line ends inside a literal.
+struct Lexer {
+    rust: bool,
+    state: State,
+}
+
+impl Lexer {
+    fn new(rust: bool) -> Self {
+        Lexer {
+            rust,
+            state: State::Code,
+        }
+    }
+
+    fn scan_line(&mut self, line: &str) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            match self.state {
+                State::Code => match c {
+                    '/' if next == Some('/') => return,
+                    '/' if next == Some('*') => {
+                        self.state = State::BlockComment(1);
+                        i += 1;
+                    }
+                    '"' => self.state = State::Str('"'),
+                    '`' if !self.rust => self.state = State::Str('`'),
+                    '\'' if !self.rust => self.state = State::Str('\''),
+                    // A char literal ('x', '\n') rather than a lifetime ('a).
+                    '\'' if next == Some('\\') => {
+                        i += 2;
+                        while i < chars.len() && chars[i] != '\'' {
+                            i += 1;
+                        }
+                    }
+                    '\'' if chars.get(i + 2) == Some(&'\'') => i += 2,
+                    'r' if self.rust && !prev_is_ident(&chars, i) => {
+                        let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+                        if chars.get(i + 1 + hashes) == Some(&'"') {
+                            self.state = State::RawStr(hashes);
+                            i += 1 + hashes;
+                        }
+                    }
+                    _ => {}
+                },
+                State::Str(quote) => {
+                    if c == '\\' {
+                        i += 1;
+                    } else if c == quote {
+                        self.state = State::Code;
+                    }
+                }
+                State::RawStr(hashes) => {
+                    if c == '"'
+                        && chars[i + 1..].iter().take_while(|&&c| c == '#').count() >= hashes
+                    {
+                        self.state = State::Code;
+                        i += hashes;
+                    }
+                }
+                State::BlockComment(depth) => {
+                    if c == '*' && next == Some('/') {
+                        self.state = if depth > 1 {
+                            State::BlockComment(depth - 1)
+                        } else {
+                            State::Code
+                        };
+                        i += 1;
+                    } else if self.rust && c == '/' && next == Some('*') {
+                        self.state = State::BlockComment(depth + 1);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+        }
+        // Single-quoted JS strings cannot span lines without a trailing backslash.
+        if matches!(self.state, State::Str('\'' | '"')) && !self.rust && !line.ends_with('\\') {
+            self.state = State::Code;
+        }
+    }
+}
+
+fn prev_is_ident(chars: &[char], i: usize) -> bool {
+    i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minify_rust_whitespace() {
+        let src = "use std::io;   \n\n\n\nfn main() {  \n    let s = \"keep   \n\n\n  inside\";\n\n\n    let r = r#\"raw  \n\"#;\n    let c = '\"';  \n}\n\n\n";
+        assert_eq!(
+            minify("rust", src, false),
+            "use std::io;\n\nfn main() {\n    let s = \"keep   \n\n\n  inside\";\n\n    let r = r#\"raw  \n\"#;\n    let c = '\"';\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_minify_rust_strip_imports() {
+        let src = "use std::{\n    fs,\n    io,\n};\nuse std::path::Path;\npub use crate::x;\n\nfn f<'a>(p: &'a Path) {}\n";
+        assert_eq!(
+            minify("rust", src, true),
+            "pub use crate::x;\n\nfn f<'a>(p: &'a Path) {}\n"
+        );
+    }
+
+    #[test]
+    fn test_minify_javascript() {
+        let src = "import fs from 'fs';\nimport {\n  a,\n  b,\n} from \"./ab\";\n\n\nconst t = `line  \n\n\nend`;  \nconst s = 'x';   \n";
+        assert_eq!(
+            minify("javascript", src, false),
+            "import fs from 'fs';\nimport {\n  a,\n  b,\n} from \"./ab\";\n\nconst t = `line  \n\n\nend`;\nconst s = 'x';\n"
+        );
+        assert_eq!(
+            minify("javascript", src, true),
+            "const t = `line  \n\n\nend`;\nconst s = 'x';\n"
+        );
+    }
+
+    #[test]
+    fn test_minify_leaves_other_languages_alone() {
+        let toml = "[package]   \n\n\n\nname = \"x\"\n";
+        assert_eq!(minify("toml", toml, true), toml);
+    }
+}
@@ -0,0 +1,127 @@
+//! Lightweight scan of a Rust file's module structure for `extract --module-map`.
+//!
+//! This is regex matching, not parsing: only unindented `mod name;` declarations and
+//! `use crate::...` imports are recognised. That is enough to sketch how the files of
+//! a crate relate to each other without compiling anything.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// The modules a Rust file declares and the crate-local paths it imports.
+#[derive(Debug, Default, PartialEq)]
+pub struct ModuleMap {
+    /// Names from `mod name;` / `pub mod name;`, in source order.
+    pub declares: Vec<String>,
+    /// First segment after `crate::` of each `use crate::...`, deduplicated.
+    pub uses: Vec<String>,
+}
+
+impl ModuleMap {
+    pub fn is_empty(&self) -> bool {
+        self.declares.is_empty() && self.uses.is_empty()
+    }
+
+    /// `declares: a, b; uses: crate::x` (`none` for an empty side).
+    pub fn annotation(&self) -> String {
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        let uses: Vec<String> = self.uses.iter().map(|u| format!("crate::{}", u)).collect();
+        format!("declares: {}; uses: {}", list(&self.declares), list(&uses))
+    }
+}
+
+/// Scans top-level `mod` declarations and `use crate::` imports of Rust `content`.
+pub fn scan(content: &str) -> ModuleMap {
+    lazy_static! {
+        static ref MOD_REGEX: Regex =
+            Regex::new(r"(?m)^(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;")
+                .unwrap();
+        static ref USE_REGEX: Regex =
+            Regex::new(r"(?m)^(?:pub(?:\([^)]*\))?\s+)?use\s+crate::([^;]*);").unwrap();
+    }
+    let declares = MOD_REGEX
+        .captures_iter(content)
+        .map(|cap| cap[1].to_string())
+        .collect();
+    let mut uses: Vec<String> = Vec::new();
+    for cap in USE_REGEX.captures_iter(content) {
+        for segment in first_segments(&cap[1]) {
+            if !uses.contains(&segment) {
+                uses.push(segment);
+            }
+        }
+    }
+    ModuleMap { declares, uses }
+}
+
+/// First path segment of each import in a use tree (`parser::X` -> `parser`,
+/// `{a::B, c}` -> `a`, `c`).
+fn first_segments(tree: &str) -> Vec<String> {
+    let tree = tree.trim();
+    let Some(group) = tree.strip_prefix('{').and_then(|t| t.strip_suffix('}')) else {
+        let head = tree.split("::").next().unwrap_or(tree);
+        let head = head.split_whitespace().next().unwrap_or(head);
+        return vec![head.to_string()];
+    };
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in group.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&group[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&group[start..]);
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty() && *item != "self")
+        .flat_map(first_segments)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_declarations_and_uses() {
+        let src = "mod cache;\npub mod parser;\npub(crate) mod util ;\n\
+                   use crate::parser::{self, ParseConfig};\n\
+                   use crate::{\n    cache::Cache,\n    util::{a, b},\n};\n\
+                   use std::fs;\n\nfn f() {\n    mod inner;\n}\n\
+                   #[cfg(test)]\nmod tests {\n    use crate::Thing;\n}\n";
+        let map = scan(src);
+        assert_eq!(map.declares, ["cache", "parser", "util"]);
+        assert_eq!(map.uses, ["parser", "cache", "util"]);
+        assert_eq!(
+            map.annotation(),
+            "declares: cache, parser, util; uses: crate::parser, crate::cache, crate::util"
+        );
+    }
+
+    #[test]
+    fn test_scan_empty() {
+        let map = scan("fn main() {}\n");
+        assert!(map.is_empty());
+        assert_eq!(
+            ModuleMap {
+                declares: vec!["a".into()],
+                uses: Vec::new()
+            }
+            .annotation(),
+            "declares: a; uses: none"
+        );
+    }
+}
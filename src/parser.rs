@@ -1,20 +1,141 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 
+/// File extensions recognised by the path-matching patterns when none are configured.
+pub const DEFAULT_EXTENSIONS: &[&str] = &["rs", "toml", "json"];
+
+/// Files recognised by name alone since they have no extension, with the fence
+/// language used when embedding them.
+pub const KNOWN_BASENAMES: &[(&str, &str)] = &[
+    ("Makefile", "makefile"),
+    ("GNUmakefile", "makefile"),
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Gemfile", "ruby"),
+    ("Rakefile", "ruby"),
+    ("Vagrantfile", "ruby"),
+    ("Justfile", "just"),
+    ("Procfile", "text"),
+    ("LICENSE", "text"),
+    ("CODEOWNERS", "text"),
+];
+
+/// Fence language of `path` if its file name is one of [`KNOWN_BASENAMES`].
+pub fn known_basename_lang(path: &str) -> Option<&'static str> {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    KNOWN_BASENAMES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, lang)| *lang)
+}
+
+/// A file block found in a Markdown document. Build one outside this crate with
+/// [`ParsedFile::new`]; more fields may be added.
 #[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
 pub struct ParsedFile {
     pub path: String,
     pub content: String,
+    /// Fence language set by a `<!-- lang: ... -->` directive just before the block,
+    /// overriding the one implied by the extension.
+    pub language: Option<String>,
+    /// Whether the block's heading carries the `[executable]` tag written by
+    /// `extract --note-exec`.
+    pub executable: bool,
+}
+
+impl ParsedFile {
+    /// A block for `path` with `content`, without a language directive or tags.
+    pub fn new(path: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            content: content.into(),
+            language: None,
+            executable: false,
+        }
+    }
+}
+
+/// Tag that `extract --note-exec` appends to the heading of an executable file.
+pub const EXECUTABLE_TAG: &str = "[executable]";
+
+/// Splits a trailing [`EXECUTABLE_TAG`] off a heading's path.
+fn split_executable_tag(path: &str) -> (String, bool) {
+    match path.trim_end().strip_suffix(EXECUTABLE_TAG) {
+        Some(rest) if rest.ends_with(char::is_whitespace) => (rest.trim_end().to_string(), true),
+        _ => (path.to_string(), false),
+    }
+}
+
+/// A unified diff for one file, taken from a ```` ```diff ```` (or ```` ```patch ````)
+/// block, to be applied to an existing file rather than overwriting it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParsedPatch {
+    /// Target path, from the `+++ b/path` header (or `--- a/path` for deletions).
+    pub path: String,
+    /// The file's part of the diff, from its `---` header through its last hunk.
+    pub diff: String,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum MdPatternType {
-    CodeTag,    // <code path="..."> ... </code>
-    HashMarker, // ### filename followed by code fence
-    Delimiter,  // ====\nfilename\n==== followed by code fence
-    Raw,        // // file: filename followed by code fence
-    FileCode,   // <file> filename </file> / <code> ... </code>
-    FileFence,  // <file>…</file> heading + fenced block
+    CodeTag,        // <code path="..."> ... </code>
+    HashMarker,     // ### filename followed by code fence
+    Delimiter,      // ====\nfilename\n==== followed by code fence
+    Raw,            // // file: filename followed by code fence
+    FileCode,       // <file> filename </file> / <code> ... </code>
+    FileFence,      // <file>…</file> heading + fenced block
+    FencedInfoPath, // ```rust path=src/main.rs (path in the fence info string)
+}
+
+/// How the content captured between fences is trimmed.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TrimMode {
+    /// Strip leading and trailing whitespace.
+    #[default]
+    Full,
+    /// Strip only trailing whitespace, keeping leading indentation.
+    Trailing,
+    /// Keep the lines between the fences verbatim.
+    None,
+}
+
+impl TrimMode {
+    fn apply(self, content: String) -> String {
+        match self {
+            TrimMode::Full => content.trim().to_string(),
+            TrimMode::Trailing => content.trim_end().to_string(),
+            TrimMode::None => content,
+        }
+    }
+}
+
+/// Options controlling how Markdown content is parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseConfig {
+    /// Use only these patterns, in priority order, instead of merging the results of
+    /// all of them; empty means every pattern.
+    pub forced: Vec<MdPatternType>,
+    /// Concatenate all fenced blocks under a single path heading (hash pattern).
+    pub merge_fences_under_heading: bool,
+    /// Extensions accepted by the raw, file-code and file-fence patterns;
+    /// `None` uses [`DEFAULT_EXTENSIONS`].
+    pub extensions: Option<Vec<String>>,
+    /// Whitespace handling of fenced content; tag-delimited content is always fully trimmed.
+    pub trim_mode: TrimMode,
+    /// Strip the leading whitespace common to every non-blank line of a block
+    /// (applied before `trim_mode`).
+    pub dedent: bool,
+    /// Keep a code fence wrapping the whole content of a `<code path>` tag instead of
+    /// stripping it, for files whose content really starts and ends with a fence.
+    pub keep_inner_fence: bool,
+    /// Pick a pattern per block instead of merging whole-document results: walk the
+    /// document once and, at each position, consume the best match of any pattern,
+    /// so blocks never overlap (see [`tokenize`]).
+    pub per_block: bool,
 }
 
 /// Parses the given markdown content and returns a vector of ParsedFile.
@@ -22,44 +143,511 @@ pub enum MdPatternType {
 /// If `forced` is provided, only that pattern is used; otherwise the parser
 /// automatically selects the pattern with the most extracted file blocks.
 pub fn parse_content(content: &str, forced: Option<MdPatternType>) -> Vec<ParsedFile> {
+    parse_content_with_config(
+        content,
+        &ParseConfig {
+            forced: forced.into_iter().collect(),
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as [`parse_content`], accepting paths with any of `extensions` in the
+/// patterns that restrict file types.
+pub fn parse_content_with_extensions(
+    content: &str,
+    forced: Option<MdPatternType>,
+    extensions: &[String],
+) -> Vec<ParsedFile> {
+    parse_content_with_config(
+        content,
+        &ParseConfig {
+            forced: forced.into_iter().collect(),
+            extensions: Some(extensions.to_vec()),
+            ..Default::default()
+        },
+    )
+}
+
+/// How blocks that one pattern finds for the same path are combined.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the first block, with a warning.
+    #[default]
+    KeepFirst,
+    /// Keep the last block, with a warning.
+    KeepLast,
+    /// Join the blocks in document order.
+    Concatenate,
+    /// Fail with a [`DuplicatePathError`].
+    Error,
+}
+
+/// A path given more than one block under [`DuplicatePolicy::Error`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuplicatePathError {
+    pub path: String,
+}
+
+impl std::fmt::Display for DuplicatePathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "more than one block for {}", self.path)
+    }
+}
+
+impl std::error::Error for DuplicatePathError {}
+
+/// A document yielding more file blocks than the configured cap.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TooManyBlocksError {
+    pub count: usize,
+    pub max: usize,
+}
+
+impl std::fmt::Display for TooManyBlocksError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} file blocks exceed the limit of {}",
+            self.count, self.max
+        )
+    }
+}
+
+impl std::error::Error for TooManyBlocksError {}
+
+/// Fails when `files` holds more than `max` blocks, guarding generation against
+/// documents that declare an unreasonable number of files.
+pub fn check_block_limit(files: &[ParsedFile], max: usize) -> Result<(), TooManyBlocksError> {
+    if files.len() > max {
+        return Err(TooManyBlocksError {
+            count: files.len(),
+            max,
+        });
+    }
+    Ok(())
+}
+
+/// Same as [`parse_content`], with the full set of parsing options.
+pub fn parse_content_with_config(content: &str, config: &ParseConfig) -> Vec<ParsedFile> {
+    parse_content_with_policy(content, config, DuplicatePolicy::KeepFirst)
+        .unwrap_or_else(|_| unreachable!("only DuplicatePolicy::Error fails"))
+}
+
+/// Same as [`parse_content_with_config`], resolving paths that one pattern matches
+/// more than once with `policy`. Different patterns matching the same block are not
+/// duplicates: the earlier pattern's block wins, as in [`parse_content_with_config`].
+pub fn parse_content_with_policy(
+    content: &str,
+    config: &ParseConfig,
+    policy: DuplicatePolicy,
+) -> Result<Vec<ParsedFile>, DuplicatePathError> {
+    let body = parse_body(content);
+    // The line-based sub-parsers share one split of the document.
+    let lines: Vec<&str> = body.lines().collect();
+    let ext = config_extensions(config);
+    let parse = |pattern| {
+        let files = run_pattern(pattern, body, &lines, config, &ext)
+            .into_iter()
+            .map(|m| m.into_file(body))
+            .collect();
+        resolve_duplicates(files, policy)
+    };
+
+    let files: Vec<ParsedFile> = if config.per_block {
+        let files = tokenize(body, &lines, config, &ext)
+            .into_iter()
+            .map(|m| m.into_file(body))
+            .collect();
+        resolve_duplicates(files, policy)?
+    } else if let [f] = config.forced[..] {
+        parse(f)?
+    } else if !config.forced.is_empty() {
+        // Earlier patterns win when several match the same path.
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for pattern in &config.forced {
+            files.extend(
+                parse(*pattern)?
+                    .into_iter()
+                    .filter(|file| seen.insert(file.path.clone())),
+            );
+        }
+        files
+    } else {
+        let mut all: Vec<ParsedFile> = ALL_PATTERNS
+            .iter()
+            .map(|p| parse(*p))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
+        // dedupe by path
+        all.sort_by(|a, b| a.path.cmp(&b.path));
+        all.dedup_by(|a, b| a.path == b.path);
+        all
+    };
+
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let content = if config.dedent {
+                dedent(&file.content)
+            } else {
+                file.content
+            };
+            ParsedFile {
+                path: file.path,
+                content: config.trim_mode.apply(content),
+                language: file.language,
+                executable: file.executable,
+            }
+        })
+        .collect())
+}
+
+/// Single-pass multi-pattern tokenizer: returns non-overlapping matches in document
+/// order. At each position the match starting earliest wins; ties go to the longest
+/// span, then to the earlier pattern. Whatever a chosen block covers is consumed, so
+/// e.g. a `### path` heading quoted inside a `<code path>` tag is not a second file.
+fn tokenize(
+    body: &str,
+    lines: &[&str],
+    config: &ParseConfig,
+    ext: &ExtensionRegexes,
+) -> Vec<Match> {
+    let patterns = if config.forced.is_empty() {
+        ALL_PATTERNS.to_vec()
+    } else {
+        config.forced.clone()
+    };
+    let mut candidates: Vec<(usize, Match)> = patterns
+        .into_iter()
+        .enumerate()
+        .flat_map(|(priority, pattern)| {
+            run_pattern(pattern, body, lines, config, ext)
+                .into_iter()
+                .map(move |m| (priority, m))
+        })
+        .collect();
+    candidates
+        .sort_by_key(|(priority, m)| (m.span.start, std::cmp::Reverse(m.span.end), *priority));
+    let mut cursor = 0;
+    let mut tokens = Vec::new();
+    for (_, m) in candidates {
+        if m.span.start >= cursor {
+            cursor = m.span.end;
+            tokens.push(m);
+        }
+    }
+    tokens
+}
+
+/// Combines the blocks of `files` that share a path according to `policy`, keeping
+/// each path at the position of its first block.
+pub fn resolve_duplicates(
+    files: Vec<ParsedFile>,
+    policy: DuplicatePolicy,
+) -> Result<Vec<ParsedFile>, DuplicatePathError> {
+    let mut resolved: Vec<ParsedFile> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for file in files {
+        let Some(&i) = index.get(&file.path) else {
+            index.insert(file.path.clone(), resolved.len());
+            resolved.push(file);
+            continue;
+        };
+        match policy {
+            DuplicatePolicy::KeepFirst => {
+                log::warn!("Duplicate block for {}; keeping the first one.", file.path);
+            }
+            DuplicatePolicy::KeepLast => {
+                log::warn!("Duplicate block for {}; keeping the last one.", file.path);
+                resolved[i] = file;
+            }
+            DuplicatePolicy::Concatenate => {
+                let content = &mut resolved[i].content;
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                content.push_str(&file.content);
+            }
+            DuplicatePolicy::Error => return Err(DuplicatePathError { path: file.path }),
+        }
+    }
+    Ok(resolved)
+}
+
+/// One sub-parser's match, as reported by [`dump_parse_tree`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct PatternMatch {
+    pub pattern: MdPatternType,
+    pub path: String,
+    /// Byte range of the whole match (heading through closing fence) in the input.
+    pub span: Range<usize>,
+    /// First line of the captured content, for orientation.
+    pub first_line: String,
+}
+
+/// Runs every sub-parser (or only the forced one) and reports what each matched,
+/// without merging or deduplicating, to debug ambiguous documents.
+pub fn dump_parse_tree(content: &str, config: &ParseConfig) -> Vec<PatternMatch> {
+    let body = parse_body(content);
+    let offset = body.as_ptr() as usize - content.as_ptr() as usize;
+    let lines: Vec<&str> = body.lines().collect();
+    let ext = config_extensions(config);
+    let patterns = if config.forced.is_empty() {
+        ALL_PATTERNS.to_vec()
+    } else {
+        config.forced.clone()
+    };
+    patterns
+        .into_iter()
+        .flat_map(|pattern| {
+            run_pattern(pattern, body, &lines, config, &ext)
+                .into_iter()
+                .map(move |m| PatternMatch {
+                    pattern,
+                    first_line: m
+                        .file
+                        .content
+                        .lines()
+                        .find(|l| !l.trim().is_empty())
+                        .unwrap_or("")
+                        .to_string(),
+                    path: m.file.path,
+                    span: m.span.start + offset..m.span.end + offset,
+                })
+        })
+        .collect()
+}
+
+/// Returns the document's text outside every matched file block: the prose around
+/// the code. Each remaining stretch is trimmed and stretches are separated by a
+/// blank line.
+pub fn residual_prose(content: &str, config: &ParseConfig) -> String {
+    let mut spans: Vec<Range<usize>> = dump_parse_tree(content, config)
+        .into_iter()
+        .map(|m| m.span)
+        .collect();
+    spans.sort_by_key(|span| span.start);
+    let mut pieces = Vec::new();
+    let mut pos = 0;
+    for span in spans {
+        if span.start > pos {
+            pieces.push(content[pos..span.start].trim());
+        }
+        pos = pos.max(span.end);
+    }
+    pieces.push(content[pos..].trim());
+    pieces.retain(|piece| !piece.is_empty());
+    pieces.join("\n\n")
+}
+
+/// The first prose paragraph before the document's first file block, skipping
+/// headings, fenced code and HTML comments; `None` if there is none.
+pub fn leading_paragraph(content: &str, config: &ParseConfig) -> Option<String> {
+    let first_block = dump_parse_tree(content, config)
+        .into_iter()
+        .map(|m| m.span.start)
+        .min()
+        .unwrap_or(content.len());
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    for line in content[..first_block].lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        let prose = !in_fence
+            && !trimmed.is_empty()
+            && !trimmed.starts_with('#')
+            && !trimmed.starts_with("<!--");
+        if prose {
+            paragraph.push(trimmed);
+        } else if !paragraph.is_empty() {
+            break;
+        }
+    }
+    (!paragraph.is_empty()).then(|| paragraph.join("\n"))
+}
+
+/// Every pattern, in the order their results are merged.
+const ALL_PATTERNS: [MdPatternType; 7] = [
+    MdPatternType::CodeTag,
+    MdPatternType::HashMarker,
+    MdPatternType::Delimiter,
+    MdPatternType::Raw,
+    MdPatternType::FileCode,
+    MdPatternType::FileFence,
+    MdPatternType::FencedInfoPath,
+];
+
+/// A file block matched by one sub-parser, with the byte range it spans in the body.
+struct Match {
+    file: ParsedFile,
+    span: Range<usize>,
+}
+
+impl Match {
+    /// The matched file, with its language taken from a `<!-- lang: ... -->` directive
+    /// on the last non-blank line before the block, if there is one.
+    fn into_file(self, body: &str) -> ParsedFile {
+        lazy_static! {
+            static ref LANG_DIRECTIVE_REGEX: Regex =
+                Regex::new(r"^\s*<!--\s*lang:\s*([A-Za-z0-9_+#.-]+)\s*-->\s*$").unwrap();
+        }
+        let before = body[..self.span.start].trim_end();
+        let last_line = before.rsplit('\n').next().unwrap_or("");
+        let language = LANG_DIRECTIVE_REGEX
+            .captures(last_line)
+            .map(|cap| cap[1].to_string());
+        ParsedFile {
+            language: language.or(self.file.language),
+            ..self.file
+        }
+    }
+}
+
+/// The part of the document the sub-parsers look at.
+fn parse_body(content: &str) -> &str {
     let content = content.trim();
-    let content = if let Some(idx) = content.find("### <file>") {
+    if let Some(idx) = content.find("### <file>") {
         &content[idx..]
     } else {
         content
     }
-    .trim();
-
-    let group1 = parse_code_tag(content);
-    let group2 = parse_hash_marker(content);
-    let group3 = parse_delimiter_marker(content);
-    let group4 = parse_raw_code_block(content);
-    let group5 = parse_file_code(content);
-    let group6 = parse_file_fence(content);
-
-    if let Some(f) = forced {
-        return match f {
-            MdPatternType::CodeTag => group1,
-            MdPatternType::HashMarker => group2,
-            MdPatternType::Delimiter => group3,
-            MdPatternType::Raw => group4,
-            MdPatternType::FileCode => group5,
-            MdPatternType::FileFence => group6,
-        };
+    .trim()
+}
+
+fn config_extensions(config: &ParseConfig) -> Arc<ExtensionRegexes> {
+    match &config.extensions {
+        Some(extensions) => extension_regexes(extensions),
+        None => {
+            lazy_static! {
+                static ref DEFAULT_REGEXES: Arc<ExtensionRegexes> = {
+                    let defaults: Vec<String> =
+                        DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect();
+                    Arc::new(ExtensionRegexes::new(&defaults))
+                };
+            }
+            Arc::clone(&DEFAULT_REGEXES)
+        }
+    }
+}
+
+fn run_pattern(
+    pattern: MdPatternType,
+    body: &str,
+    lines: &[&str],
+    config: &ParseConfig,
+    ext: &ExtensionRegexes,
+) -> Vec<Match> {
+    match pattern {
+        MdPatternType::CodeTag => parse_code_tag(body, ext, config.keep_inner_fence),
+        MdPatternType::HashMarker => {
+            parse_hash_marker(lines, ext, config.merge_fences_under_heading)
+        }
+        MdPatternType::Delimiter => parse_delimiter_marker(lines),
+        MdPatternType::Raw => parse_raw_code_block(lines, ext),
+        MdPatternType::FileCode => parse_file_code(body, ext),
+        MdPatternType::FileFence => parse_file_fence(lines, ext),
+        MdPatternType::FencedInfoPath => parse_fenced_info_path(lines),
+    }
+}
+
+/// Byte range covering `lines[start..end]` within the text the lines were split from.
+fn lines_span(lines: &[&str], start: usize, end: usize) -> Range<usize> {
+    let base = lines[0].as_ptr() as usize;
+    let last = lines[end.max(start + 1) - 1];
+    let from = lines[start].as_ptr() as usize - base;
+    let to = last.as_ptr() as usize - base + last.len();
+    from..to
+}
+
+/// Path and executable flag captured by a header regex whose groups are
+/// `(path)(tag)?` for a recognised path or `(path)(tag)` for any path carrying the
+/// `[executable]` tag, which only `extract --note-exec` writes.
+fn tagged_path(cap: &regex::Captures) -> (String, bool) {
+    let path = cap.get(1).or_else(|| cap.get(3)).map_or("", |m| m.as_str());
+    (
+        path.trim().to_string(),
+        cap.get(2).is_some() || cap.get(4).is_some(),
+    )
+}
+
+/// Path-matching regexes that depend on the configured extension set. A heading
+/// tagged `[executable]` is accepted whatever its extension.
+struct ExtensionRegexes {
+    /// Hash heading whose path follows a single label or emoji token (`File:`, `📄`).
+    labelled_header: Regex,
+    raw_header: Regex,
+    file_tag: Regex,
+    file_heading: Regex,
+    /// `<code path="...">...</code>`, with the content in group 5.
+    code_tag: Regex,
+}
+
+impl ExtensionRegexes {
+    fn new(extensions: &[String]) -> Self {
+        let alternation = extensions
+            .iter()
+            .map(|e| regex::escape(e))
+            .collect::<Vec<_>>()
+            .join("|");
+        let basenames = basename_alternation();
+        Self {
+            labelled_header: Regex::new(&format!(
+                r"^\s*#{{1,6}}\s+(?:[^\s`]+\s+)?`?([^\s`]+\.(?i:{0})|(?:[^\s`]*/)?(?:{1}))`?\s*$",
+                alternation, basenames
+            ))
+            .unwrap(),
+            raw_header: Regex::new(&format!(
+                r"^\s*//\s*file:\s*(?:([^\s]+\.(?i:{0})|(?:[^\s]*/)?(?:{1}))(?:[ \t]+(\[executable\]))?|(\S+)[ \t]+(\[executable\]))\s*$",
+                alternation, basenames
+            ))
+            .unwrap(),
+            file_tag: Regex::new(&format!(
+                r"(?is)<file>\s*(?:([^<>\r\n]+?\.(?:{0})|(?:[^<>\s]*/)?(?-i:{1}))\s*</file>(?:[ \t]*(\[executable\]))?|([^<>\s]+)\s*</file>[ \t]*(\[executable\]))",
+                alternation, basenames
+            ))
+            .unwrap(),
+            file_heading: Regex::new(&format!(
+                r"(?i)^\s*#{{1,6}}\s*<file>\s*(?:([^\s<>]+?\.(?:{0})|(?:[^\s<>]*/)?(?-i:{1}))\s*</file>(?:[ \t]+(\[executable\]))?|([^\s<>]+)\s*</file>[ \t]+(\[executable\]))\s*$",
+                alternation, basenames
+            ))
+            .unwrap(),
+            code_tag: Regex::new(&format!(
+                r#"(?is)<code\s+path\s*=\s*"(?:([^"\r\n]+?\.(?:{0})|(?:[^"\s]*/)?(?-i:{1}))">(?:[ \t]*(\[executable\]))?|([^"\r\n]+)">[ \t]*(\[executable\]))\s*(.*?)\s*</code>"#,
+                alternation, basenames
+            ))
+            .unwrap(),
+        }
     }
+}
 
-    let mut all = Vec::new();
-    all.extend(group1);
-    all.extend(group2);
-    all.extend(group3);
-    all.extend(group4);
-    all.extend(group5);
-    all.extend(group6);
+/// `Makefile|Dockerfile|...`, for matching [`KNOWN_BASENAMES`] in path regexes.
+fn basename_alternation() -> String {
+    KNOWN_BASENAMES
+        .iter()
+        .map(|(name, _)| regex::escape(name))
+        .collect::<Vec<_>>()
+        .join("|")
+}
 
-    // dedupe by path
-    all.sort_by(|a, b| a.path.cmp(&b.path));
-    all.dedup_by(|a, b| a.path == b.path);
-    all
+/// Returns the compiled regexes for `extensions`, compiling them only the first
+/// time a given extension set is seen.
+fn extension_regexes(extensions: &[String]) -> Arc<ExtensionRegexes> {
+    lazy_static! {
+        static ref CACHE: Mutex<HashMap<Vec<String>, Arc<ExtensionRegexes>>> =
+            Mutex::new(HashMap::new());
+    }
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(regexes) = cache.get(extensions) {
+        return Arc::clone(regexes);
+    }
+    let regexes = Arc::new(ExtensionRegexes::new(extensions));
+    cache.insert(extensions.to_vec(), Arc::clone(&regexes));
+    regexes
 }
 
 /// Sub-parser 1: XML-like code block pattern.
@@ -68,35 +656,37 @@ pub fn parse_content(content: &str, forced: Option<MdPatternType>) -> Vec<Parsed
 ///     [package]
 ///     name = "example"
 ///     </code>
-fn parse_code_tag(content: &str) -> Vec<ParsedFile> {
-    lazy_static! {
-        static ref CODE_TAG_REGEX: Regex = Regex::new(
-            r#"(?is)<code\s+path\s*=\s*"([^"\r\n]+?\.(?:rs|toml|json))">\s*(.*?)\s*</code>"#
-        )
-        .unwrap();
-    }
+///
+/// A code fence wrapping the content is stripped unless `keep_inner_fence` is set.
+fn parse_code_tag(content: &str, ext: &ExtensionRegexes, keep_inner_fence: bool) -> Vec<Match> {
     let mut results = Vec::new();
-    for cap in CODE_TAG_REGEX.captures_iter(content) {
-        let path = cap[1].trim().to_string();
-        let mut code = cap[2].trim().to_string();
+    for cap in ext.code_tag.captures_iter(content) {
+        let span = cap.get(0).unwrap().range();
+        let (path, executable) = tagged_path(&cap);
+        let mut code = cap[5].trim().to_string();
 
         // If the captured code starts with a code fence, remove it.
-        if code.starts_with("```") {
+        if !keep_inner_fence && code.starts_with("```") {
             // Remove the first line (the opening fence with optional language).
             if let Some(pos) = code.find('\n') {
                 code = code[pos..].trim_start().to_string();
             }
             // If the code ends with a closing fence, remove it.
-            if code.ends_with("```") {
-                if let Some(pos) = code.rfind("```") {
-                    code = code[..pos].trim_end().to_string();
-                }
+            if code.ends_with("```")
+                && let Some(pos) = code.rfind("```")
+            {
+                code = code[..pos].trim_end().to_string();
             }
         }
 
-        results.push(ParsedFile {
-            path,
-            content: code,
+        results.push(Match {
+            file: ParsedFile {
+                path,
+                content: code,
+                language: None,
+                executable,
+            },
+            span,
         });
     }
     results
@@ -114,9 +704,17 @@ fn parse_code_tag(content: &str) -> Vec<ParsedFile> {
 ///     [package]
 ///     name = "example"
 ///     ```
-fn parse_hash_marker(content: &str) -> Vec<ParsedFile> {
+/// and a label or emoji before the path:
+///     ### File: src/main.rs
+///     ### 📄 src/main.rs
+///
+/// A heading whose text has a space in it and is not such a labelled path
+/// (`## Version 1.2`) is prose, not a file.
+///
+/// With `merge_fences`, every fenced block following the heading (skipping any
+/// interleaved prose) is concatenated into the same file until the next heading.
+fn parse_hash_marker(lines: &[&str], ext: &ExtensionRegexes, merge_fences: bool) -> Vec<Match> {
     let mut results = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
     let mut idx = 0;
 
     lazy_static! {
@@ -127,8 +725,17 @@ fn parse_hash_marker(content: &str) -> Vec<ParsedFile> {
 
     while idx < lines.len() {
         let line = lines[idx];
-        if let Some(cap) = HASH_HEADER_REGEX.captures(line) {
-            let file_path = cap[1].to_string();
+        if let Some((file_path, executable)) = ext
+            .labelled_header
+            .captures(line)
+            .or_else(|| HASH_HEADER_REGEX.captures(line))
+            // `### <file> path </file>` headings belong to the file-fence pattern
+            .filter(|cap| !cap[1].trim_start().starts_with("<file>"))
+            // Prose headings (`## Version 1.2`) are not paths
+            .map(|cap| split_executable_tag(&cap[1]))
+            .filter(|(path, _)| !path.trim().contains(char::is_whitespace))
+        {
+            let start = idx;
             idx += 1;
 
             while idx < lines.len() && lines[idx].trim().is_empty() {
@@ -137,22 +744,32 @@ fn parse_hash_marker(content: &str) -> Vec<ParsedFile> {
 
             if idx < lines.len() && CODE_FENCE_REGEX.is_match(lines[idx]) {
                 idx += 1; // Skip the opening fence
-                let mut code = String::new();
-                while idx < lines.len() && !CODE_FENCE_REGEX.is_match(lines[idx]) {
-                    code.push_str(lines[idx]);
-                    code.push('\n');
-                    idx += 1;
-                }
-                if idx < lines.len() && CODE_FENCE_REGEX.is_match(lines[idx]) {
-                    idx += 1; // Skip the closing fence
+                let (mut code, new_idx) = extract_code_block(lines, idx);
+                idx = new_idx;
+                if merge_fences {
+                    // Keep collecting fences until the next heading, skipping prose.
+                    while idx < lines.len() && !HASH_HEADER_REGEX.is_match(lines[idx]) {
+                        if CODE_FENCE_REGEX.is_match(lines[idx]) {
+                            let (more, new_idx) = extract_code_block(lines, idx + 1);
+                            code.push_str(&more);
+                            idx = new_idx;
+                        } else {
+                            idx += 1;
+                        }
+                    }
                 }
-                results.push(ParsedFile {
-                    path: file_path,
-                    content: code.trim().to_string(),
+                results.push(Match {
+                    file: ParsedFile {
+                        path: file_path,
+                        content: code,
+                        language: None,
+                        executable,
+                    },
+                    span: lines_span(lines, start, idx),
                 });
-            } else {
-                idx += 1;
             }
+            // Otherwise the heading had no fence; resume at the line after it, which
+            // may itself be a file heading.
         } else {
             idx += 1;
         }
@@ -168,40 +785,47 @@ fn parse_hash_marker(content: &str) -> Vec<ParsedFile> {
 ///     ```rust
 ///     pub fn lib_function() {}
 ///     ```
-fn parse_delimiter_marker(content: &str) -> Vec<ParsedFile> {
+fn parse_delimiter_marker(lines: &[&str]) -> Vec<Match> {
     let mut results = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
     let mut idx = 0;
     lazy_static! {
         static ref CODE_FENCE_REGEX: Regex = Regex::new(r"^\s*```(?:[a-zA-Z0-9]*)\s*$").unwrap();
     }
     while idx < lines.len() {
         let line = lines[idx];
-        if line.trim().chars().all(|c| c == '=') && !line.trim().is_empty() {
-            if idx + 2 < lines.len() {
-                let candidate = lines[idx + 1].trim();
-                if candidate.ends_with(".rs")
-                    || candidate.ends_with(".toml")
-                    || candidate.ends_with(".json")
-                {
-                    let delim_line = lines[idx + 2].trim();
-                    if delim_line.chars().all(|c| c == '=') && !delim_line.is_empty() {
-                        let file_path = candidate.to_string();
-                        idx += 3; // skip header lines
-                        while idx < lines.len() && lines[idx].trim().is_empty() {
-                            idx += 1;
-                        }
-                        if idx < lines.len() && CODE_FENCE_REGEX.is_match(lines[idx]) {
-                            idx += 1; // skip opening fence
-                            let (code, new_idx) = extract_code_block(&lines, idx);
-                            idx = new_idx;
-                            results.push(ParsedFile {
-                                path: file_path,
-                                content: code.trim().to_string(),
-                            });
-                            continue;
-                        }
-                    }
+        if line.trim().chars().all(|c| c == '=') && !line.trim().is_empty() && idx + 2 < lines.len()
+        {
+            let (candidate, executable) = split_executable_tag(lines[idx + 1].trim());
+            let candidate = candidate.as_str();
+            let delim_line = lines[idx + 2].trim();
+            if (executable
+                || candidate.ends_with(".rs")
+                || candidate.ends_with(".toml")
+                || candidate.ends_with(".json")
+                || known_basename_lang(candidate).is_some())
+                && delim_line.chars().all(|c| c == '=')
+                && !delim_line.is_empty()
+            {
+                let file_path = candidate.to_string();
+                let start = idx;
+                idx += 3; // skip header lines
+                while idx < lines.len() && lines[idx].trim().is_empty() {
+                    idx += 1;
+                }
+                if idx < lines.len() && CODE_FENCE_REGEX.is_match(lines[idx]) {
+                    idx += 1; // skip opening fence
+                    let (code, new_idx) = extract_code_block(lines, idx);
+                    idx = new_idx;
+                    results.push(Match {
+                        file: ParsedFile {
+                            path: file_path,
+                            content: code,
+                            language: None,
+                            executable,
+                        },
+                        span: lines_span(lines, start, idx),
+                    });
+                    continue;
                 }
             }
         }
@@ -216,30 +840,33 @@ fn parse_delimiter_marker(content: &str) -> Vec<ParsedFile> {
 ///     ```rust
 ///     pub fn util() {}
 ///     ```
-fn parse_raw_code_block(content: &str) -> Vec<ParsedFile> {
+fn parse_raw_code_block(lines: &[&str], ext: &ExtensionRegexes) -> Vec<Match> {
     let mut results = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
     let mut idx = 0;
     lazy_static! {
-        static ref RAW_HEADER_REGEX: Regex =
-            Regex::new(r"^\s*//\s*file:\s*([^\s]+\.(?:rs|toml|json))\s*$").unwrap();
         static ref CODE_FENCE_REGEX: Regex = Regex::new(r"^\s*```(?:[a-zA-Z0-9]*)\s*$").unwrap();
     }
     while idx < lines.len() {
         let line = lines[idx];
-        if let Some(cap) = RAW_HEADER_REGEX.captures(line) {
-            let file_path = cap[1].trim().to_string();
+        if let Some(cap) = ext.raw_header.captures(line) {
+            let (file_path, executable) = tagged_path(&cap);
+            let start = idx;
             idx += 1;
             while idx < lines.len() && lines[idx].trim().is_empty() {
                 idx += 1;
             }
             if idx < lines.len() && CODE_FENCE_REGEX.is_match(lines[idx]) {
                 idx += 1; // skip opening fence
-                let (code, new_idx) = extract_code_block(&lines, idx);
+                let (code, new_idx) = extract_code_block(lines, idx);
                 idx = new_idx;
-                results.push(ParsedFile {
-                    path: file_path,
-                    content: code.trim().to_string(),
+                results.push(Match {
+                    file: ParsedFile {
+                        path: file_path,
+                        content: code,
+                        language: None,
+                        executable,
+                    },
+                    span: lines_span(lines, start, idx),
                 });
                 continue;
             }
@@ -257,27 +884,30 @@ fn parse_raw_code_block(content: &str) -> Vec<ParsedFile> {
 ///     name = "trait_enforcement_demo"
 ///     ...
 ///     </code>
-fn parse_file_code(content: &str) -> Vec<ParsedFile> {
+fn parse_file_code(content: &str, ext: &ExtensionRegexes) -> Vec<Match> {
     let mut results = Vec::new();
     lazy_static! {
-        static ref FILE_TAG_REGEX: Regex =
-            Regex::new(r#"(?is)<file>\s*([^<>\r\n]+?\.(?:rs|toml|json))\s*</file>"#).unwrap();
         static ref CODE_BLOCK_REGEX: Regex =
             Regex::new(r#"(?is)<code>\s*(.*?)\s*</code>"#).unwrap();
     }
     let mut files = Vec::new();
-    for cap in FILE_TAG_REGEX.captures_iter(content) {
-        files.push(cap[1].trim().to_string());
+    for cap in ext.file_tag.captures_iter(content) {
+        let (path, executable) = tagged_path(&cap);
+        files.push((path, executable, cap.get(0).unwrap().start()));
     }
     let mut codes = Vec::new();
     for cap in CODE_BLOCK_REGEX.captures_iter(content) {
-        codes.push(cap[1].trim().to_string());
+        codes.push((cap[1].trim().to_string(), cap.get(0).unwrap().end()));
     }
-    let count = files.len().min(codes.len());
-    for i in 0..count {
-        results.push(ParsedFile {
-            path: files[i].clone(),
-            content: codes[i].clone(),
+    for ((path, executable, start), (code, end)) in files.into_iter().zip(codes) {
+        results.push(Match {
+            file: ParsedFile {
+                path,
+                content: code,
+                language: None,
+                executable,
+            },
+            span: start..end.max(start),
         });
     }
     results
@@ -289,21 +919,18 @@ fn parse_file_code(content: &str) -> Vec<ParsedFile> {
 /// ```rust
 /// pub fn foo() {}
 /// ```
-fn parse_file_fence(content: &str) -> Vec<ParsedFile> {
+fn parse_file_fence(lines: &[&str], ext: &ExtensionRegexes) -> Vec<Match> {
     let mut results = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
     let mut idx = 0;
 
     lazy_static! {
-        static ref FILE_HEADING_REGEX: Regex =
-            Regex::new(r"(?i)^\s*#{1,6}\s*<file>\s*([^\s<>]+?\.(?:rs|toml|json))\s*</file>\s*$")
-                .unwrap();
         static ref OPEN_FENCE_REGEX: Regex = Regex::new(r"^\s*```").unwrap();
     }
 
     while idx < lines.len() {
-        if let Some(cap) = FILE_HEADING_REGEX.captures(lines[idx]) {
-            let file_path = cap[1].trim().to_string();
+        if let Some(cap) = ext.file_heading.captures(lines[idx]) {
+            let (file_path, executable) = tagged_path(&cap);
+            let start = idx;
             idx += 1;
 
             // skip blank lines
@@ -337,10 +964,15 @@ fn parse_file_fence(content: &str) -> Vec<ParsedFile> {
                 }
 
                 // join, trim, and push
-                let code = code_lines.join("\n").trim().to_string();
-                results.push(ParsedFile {
-                    path: file_path,
-                    content: code,
+                let code = join_lines(&code_lines);
+                results.push(Match {
+                    file: ParsedFile {
+                        path: file_path,
+                        content: code,
+                        language: None,
+                        executable,
+                    },
+                    span: lines_span(lines, start, idx),
                 });
                 continue;
             }
@@ -351,14 +983,240 @@ fn parse_file_fence(content: &str) -> Vec<ParsedFile> {
     results
 }
 
+/// Pattern 7: path declared in the info string of the fence itself.
+/// Example:
+///     ```rust path=src/main.rs
+///     pub fn run() {}
+///     ```
+///
+/// The fence may use four or more backticks so that the content can itself contain
+/// triple-backtick fences; only a bare fence at least as long as the opener closes it.
+fn parse_fenced_info_path(lines: &[&str]) -> Vec<Match> {
+    lazy_static! {
+        static ref INFO_OPEN_REGEX: Regex =
+            Regex::new(r#"^\s*(`{3,})[^`]*?\bpath\s*=\s*"?([^"\s`]+)"?[^`]*$"#).unwrap();
+        static ref BARE_FENCE_REGEX: Regex = Regex::new(r"^\s*(`{3,})\s*$").unwrap();
+    }
+    let mut results = Vec::new();
+    let mut idx = 0;
+
+    while idx < lines.len() {
+        if let Some(cap) = INFO_OPEN_REGEX.captures(lines[idx]) {
+            let fence_len = cap[1].len();
+            let file_path = cap[2].to_string();
+            let executable = lines[idx].trim_end().ends_with(EXECUTABLE_TAG);
+            let start = idx;
+            idx += 1;
+            let mut code_lines = Vec::new();
+            while idx < lines.len() {
+                let closes = BARE_FENCE_REGEX
+                    .captures(lines[idx])
+                    .is_some_and(|c| c[1].len() >= fence_len);
+                idx += 1;
+                if closes {
+                    break;
+                }
+                code_lines.push(lines[idx - 1]);
+            }
+            results.push(Match {
+                file: ParsedFile {
+                    path: file_path,
+                    content: join_lines(&code_lines),
+                    language: None,
+                    executable,
+                },
+                span: lines_span(lines, start, idx),
+            });
+            continue;
+        }
+        idx += 1;
+    }
+    results
+}
+
+/// Collects the per-file patches from every `diff`/`patch` fenced block in `content`.
+///
+/// A block may hold several files; each starts at a `--- a/path` line followed by
+/// `+++ b/path` and runs until the next file's `diff --git` line or header. Lines
+/// outside a file's part (e.g. `diff --git`, `index ...`) are ignored.
+pub fn parse_patches(content: &str) -> Vec<ParsedPatch> {
+    lazy_static! {
+        static ref DIFF_FENCE_REGEX: Regex = Regex::new(r"^\s*```\s*(?:diff|patch)\s*$").unwrap();
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let mut patches = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if !DIFF_FENCE_REGEX.is_match(lines[idx]) {
+            idx += 1;
+            continue;
+        }
+        let (body, next) = extract_code_block(&lines, idx + 1);
+        idx = next;
+        let body: Vec<&str> = body.lines().collect();
+        let mut i = 0;
+        while i < body.len() {
+            let header = body[i].starts_with("--- ")
+                && body.get(i + 1).is_some_and(|l| l.starts_with("+++ "));
+            if !header {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            i += 2;
+            while i < body.len()
+                && !body[i].starts_with("diff --git ")
+                && !(body[i].starts_with("--- ")
+                    && body.get(i + 1).is_some_and(|l| l.starts_with("+++ ")))
+            {
+                i += 1;
+            }
+            let old = diff_header_path(&body[start][4..], "a/");
+            let new = diff_header_path(&body[start + 1][4..], "b/");
+            let path = if new == "/dev/null" { old } else { new };
+            patches.push(ParsedPatch {
+                path: path.to_string(),
+                diff: join_lines(&body[start..i]),
+            });
+        }
+    }
+    patches
+}
+
+/// The path in a `---`/`+++` header, without its `a/`/`b/` prefix or trailing timestamp.
+fn diff_header_path<'a>(header: &'a str, prefix: &str) -> &'a str {
+    let path = header.split('\t').next().unwrap_or(header).trim();
+    path.strip_prefix(prefix).unwrap_or(path)
+}
+
+/// Counts the file blocks [`parse_content_with_config`] returns for `content`. The
+/// blocks are still parsed in full; only the count is kept.
+pub fn count_blocks(content: &str, config: &ParseConfig) -> usize {
+    parse_content_with_config(content, config).len()
+}
+
+/// Returns the pattern matching the most file blocks in `content`, with that count;
+/// ties go to the pattern listed first. `None` when no pattern matches anything.
+///
+/// Blocks sharing a path count once, as in [`count_blocks`] with that pattern forced.
+pub fn detect_best_pattern(content: &str, config: &ParseConfig) -> Option<(MdPatternType, usize)> {
+    let mut best: Option<(MdPatternType, usize)> = None;
+    for pattern in ALL_PATTERNS {
+        let forced = ParseConfig {
+            forced: vec![pattern],
+            ..config.clone()
+        };
+        let count = count_blocks(content, &forced);
+        if count > 0 && best.is_none_or(|(_, most)| count > most) {
+            best = Some((pattern, count));
+        }
+    }
+    best
+}
+
+/// Removes the longest run of leading whitespace shared by all non-blank lines.
+///
+/// Blank lines do not constrain the prefix and are emptied of whitespace.
+pub fn dedent(content: &str) -> String {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut prefix: Option<&str> = None;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let own = &line[..indent(line)];
+        prefix = Some(match prefix {
+            None => own,
+            Some(prefix) => {
+                let common = prefix
+                    .char_indices()
+                    .zip(own.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(prefix.len().min(own.len()), |((i, _), _)| i);
+                &prefix[..common]
+            }
+        });
+    }
+    let prefix = prefix.unwrap_or("");
+    if prefix.is_empty() {
+        return content.to_string();
+    }
+    content
+        .split_inclusive('\n')
+        .map(|line| match line.strip_prefix(prefix) {
+            Some(rest) => rest,
+            None => line.trim_start_matches([' ', '\t']),
+        })
+        .collect()
+}
+
+/// Splits a document into `(name, section)` pairs at each top-level `# Name` heading.
+///
+/// Headings inside fenced code blocks are ignored, as is any content before the
+/// first heading. Returns an empty vector when the document has no H1 heading.
+pub fn split_on_h1(content: &str) -> Vec<(String, String)> {
+    lazy_static! {
+        static ref H1_REGEX: Regex = Regex::new(r"^#\s+(.+?)\s*#*\s*$").unwrap();
+        static ref FENCE_REGEX: Regex = Regex::new(r"^\s*```").unwrap();
+    }
+    let mut sections: Vec<(String, String)> = Vec::new();
+    let mut in_fence = false;
+    for line in content.lines() {
+        if FENCE_REGEX.is_match(line) {
+            in_fence = !in_fence;
+        } else if !in_fence && let Some(cap) = H1_REGEX.captures(line) {
+            sections.push((cap[1].to_string(), String::new()));
+            continue;
+        }
+        if let Some((_, section)) = sections.last_mut() {
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+    sections
+}
+
+/// Fallback used when no pattern matched: treats the first fenced block (or, if the
+/// document has no fence, the whole content) as a single file written to `path`.
+///
+/// Returns `None` when there is nothing to write.
+pub fn parse_fallback_single(content: &str, path: &str) -> Option<ParsedFile> {
+    lazy_static! {
+        static ref OPEN_FENCE_REGEX: Regex = Regex::new(r"^\s*```").unwrap();
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let code = match lines.iter().position(|l| OPEN_FENCE_REGEX.is_match(l)) {
+        Some(start) => extract_code_block(&lines, start + 1).0,
+        None => content.to_string(),
+    };
+    let code = code.trim();
+    if code.is_empty() {
+        return None;
+    }
+    Some(ParsedFile::new(path, code))
+}
+
 /// Helper: extracts code lines from `lines` starting at idx until a closing code fence is found (or EOF).
+///
+/// Any line starting with ``` and containing no further backticks closes the block, even
+/// when it carries a stray info string (e.g. ```` ```end ````); such closers are reported.
+///
+/// When the opening fence (the line before `idx`) is indented and every non-blank code
+/// line carries that same indentation, it is stripped, so blocks nested in lists or
+/// indented documents come out at column 0.
 fn extract_code_block(lines: &[&str], mut idx: usize) -> (String, usize) {
     lazy_static! {
-        static ref CODE_FENCE_REGEX: Regex = Regex::new(r"^\s*```(?:[a-zA-Z0-9]*)\s*$").unwrap();
+        static ref CLOSING_FENCE_REGEX: Regex = Regex::new(r"^\s*```([^`]*)$").unwrap();
     }
+    let start = idx;
     let mut code_lines = Vec::new();
     while idx < lines.len() {
-        if CODE_FENCE_REGEX.is_match(lines[idx]) {
+        if let Some(cap) = CLOSING_FENCE_REGEX.captures(lines[idx]) {
+            let info = cap[1].trim();
+            if !info.is_empty() {
+                log::warn!(
+                    "Closing fence on line {} carries an info string ({:?}); treating it as a closer.",
+                    idx + 1,
+                    info
+                );
+            }
             idx += 1;
             break;
         } else {
@@ -366,7 +1224,30 @@ fn extract_code_block(lines: &[&str], mut idx: usize) -> (String, usize) {
             idx += 1;
         }
     }
-    (code_lines.join("\n"), idx)
+    let fence_indent = start
+        .checked_sub(1)
+        .and_then(|open| lines.get(open))
+        .map_or("", |fence| &fence[..fence.len() - fence.trim_start().len()]);
+    let uniform = !fence_indent.is_empty()
+        && code_lines
+            .iter()
+            .all(|line| line.trim().is_empty() || line.starts_with(fence_indent));
+    if uniform {
+        for line in &mut code_lines {
+            *line = line.strip_prefix(fence_indent).unwrap_or(line.trim_start());
+        }
+    }
+    (join_lines(&code_lines), idx)
+}
+
+/// Joins block lines back into text, ending each line with a newline.
+fn join_lines(lines: &[&str]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
 }
 
 #[cfg(test)]
@@ -374,6 +1255,60 @@ mod tests {
     use super::*;
     use indoc::indoc;
 
+    #[test]
+    fn test_parse_patches_from_diff_blocks() {
+        let md = indoc! {r#"
+            Bump the greeting and drop the helper:
+
+            ```diff
+            diff --git a/src/main.rs b/src/main.rs
+            --- a/src/main.rs
+            +++ b/src/main.rs
+            @@ -1,3 +1,3 @@
+             fn main() {
+            -    println!("Hello");
+            +    println!("Hello, world");
+             }
+            diff --git a/src/lib.rs b/src/lib.rs
+            index 3..4 100644
+            --- a/src/lib.rs
+            +++ b/src/lib.rs
+            @@ -1 +1 @@
+            -pub fn a() {}
+            +pub fn b() {}
+            --- a/src/helper.rs	2024-01-01 00:00:00
+            +++ /dev/null
+            @@ -1 +0,0 @@
+            -pub fn help() {}
+            ```
+
+            ```rust
+            --- not a patch
+            +++ at all
+            ```
+        "#};
+        let patches = parse_patches(md);
+        assert_eq!(patches.len(), 3);
+        assert_eq!(patches[0].path, "src/main.rs");
+        assert_eq!(
+            patches[0].diff,
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,3 +1,3 @@\n fn main() {\n\
+             -    println!(\"Hello\");\n+    println!(\"Hello, world\");\n }\n"
+        );
+        assert_eq!(patches[1].path, "src/lib.rs");
+        assert_eq!(
+            patches[1].diff,
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-pub fn a() {}\n+pub fn b() {}\n"
+        );
+        assert_eq!(patches[2].path, "src/helper.rs");
+        assert!(
+            patches[2]
+                .diff
+                .ends_with("@@ -1 +0,0 @@\n-pub fn help() {}\n")
+        );
+        assert!(parse_content(md, None).is_empty());
+    }
+
     #[test]
     fn test_code_tag_pattern() {
         let md = indoc! {r#"
@@ -393,16 +1328,149 @@ mod tests {
     }
 
     #[test]
-    fn test_hash_marker_pattern() {
+    fn test_leading_paragraph() {
         let md = indoc! {r#"
+            # Demo
+
+            <!-- generated -->
+
+            A tiny CLI that greets
+            whoever runs it.
+
+            Second paragraph.
+
             ### src/main.rs
             ```rust
-            fn main() { println!("Hello, world!"); }
+            fn main() {}
             ```
         "#};
-        let parsed = parse_content(md, None);
+        let config = ParseConfig::default();
         assert_eq!(
-            parsed.len(),
+            leading_paragraph(md, &config).as_deref(),
+            Some("A tiny CLI that greets\nwhoever runs it.")
+        );
+        let code_first = "### src/main.rs\n```rust\nfn main() {}\n```\n\nTrailing prose.\n";
+        assert_eq!(leading_paragraph(code_first, &config), None);
+    }
+
+    #[test]
+    fn test_per_block_mixed_styles() {
+        let md = indoc! {r#"
+            ### src/main.rs
+            ```rust
+            fn main() {}
+            ```
+
+            <code path="src/lib.rs">
+            //! Quotes another style:
+            ### src/quoted.rs
+            ```rust
+            pub fn quoted() {}
+            ```
+            </code>
+
+            // file: Cargo.toml
+            ```toml
+            [package]
+            name = "mixed"
+            ```
+        "#};
+        let config = ParseConfig {
+            per_block: true,
+            ..Default::default()
+        };
+        let files = parse_content_with_config(md, &config);
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/main.rs", "src/lib.rs", "Cargo.toml"]);
+        assert!(files[1].content.contains("pub fn quoted() {}"));
+        assert_eq!(files[2].content, "[package]\nname = \"mixed\"");
+
+        // Merging whole-document results also picks up the quoted heading.
+        let merged = parse_content_with_config(md, &ParseConfig::default());
+        assert!(merged.iter().any(|f| f.path == "src/quoted.rs"));
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let md = indoc! {r#"
+            ### src/main.rs
+            ```rust
+            fn main() {}
+            ```
+
+            ### src/lib.rs
+            ```rust
+            pub fn lib() {}
+            ```
+
+            ### src/main.rs
+            ```rust
+            fn helper() {}
+            ```
+        "#};
+        let config = ParseConfig::default();
+        let parse = |policy| parse_content_with_policy(md, &config, policy);
+
+        let first = parse(DuplicatePolicy::KeepFirst).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[1].content, "fn main() {}");
+        let last = parse(DuplicatePolicy::KeepLast).unwrap();
+        assert_eq!(last[1].content, "fn helper() {}");
+        let joined = parse(DuplicatePolicy::Concatenate).unwrap();
+        assert_eq!(joined[1].content, "fn main() {}\nfn helper() {}");
+        assert_eq!(
+            parse(DuplicatePolicy::Error),
+            Err(DuplicatePathError {
+                path: "src/main.rs".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_block_limit() {
+        let md: String = (0..3)
+            .map(|i| format!("### src/f{}.rs\n```rust\nfn f() {{}}\n```\n\n", i))
+            .collect();
+        let files = parse_content(&md, None);
+        assert_eq!(files.len(), 3);
+        assert_eq!(check_block_limit(&files, 3), Ok(()));
+        let err = check_block_limit(&files, 2).unwrap_err();
+        assert_eq!(err, TooManyBlocksError { count: 3, max: 2 });
+        assert_eq!(err.to_string(), "3 file blocks exceed the limit of 2");
+    }
+
+    #[test]
+    fn test_code_tag_inner_fence_stripping() {
+        let md = indoc! {r#"
+            <code path="src/snippet.rs">
+            ```md
+            # Title
+            ```
+            </code>
+        "#};
+        let parsed = parse_content(md, Some(MdPatternType::CodeTag));
+        assert_eq!(parsed[0].content, "# Title");
+
+        let config = ParseConfig {
+            forced: vec![MdPatternType::CodeTag],
+            keep_inner_fence: true,
+            ..Default::default()
+        };
+        let parsed = parse_content_with_config(md, &config);
+        assert_eq!(parsed[0].content, "```md\n# Title\n```");
+    }
+
+    #[test]
+    fn test_hash_marker_pattern() {
+        let md = indoc! {r#"
+            ### src/main.rs
+            ```rust
+            fn main() { println!("Hello, world!"); }
+            ```
+        "#};
+        let parsed = parse_content(md, None);
+        assert_eq!(
+            parsed.len(),
             1,
             "Expected one parsed file for hash marker pattern"
         );
@@ -447,6 +1515,86 @@ mod tests {
         assert!(parsed[0].content.contains("pub fn util() {}"));
     }
 
+    #[test]
+    fn test_hash_marker_heading_right_after_fenceless_heading() {
+        let md = "# Demo\n\n### Cargo.toml\n```toml\n[package]\n```\n\
+                  ## Notes\n### src/main.rs\n```rust\nfn main() {}\n```\n";
+        let parsed = parse_content(md, Some(MdPatternType::HashMarker));
+        let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["Cargo.toml", "src/main.rs"]);
+    }
+
+    #[test]
+    fn test_extensions_match_case_insensitively() {
+        let raw = parse_content(
+            "// file: SRC/MAIN.RS\n```rust\nfn main() {}\n```\n",
+            Some(MdPatternType::Raw),
+        );
+        assert_eq!(raw.len(), 1);
+        assert_eq!(raw[0].path, "SRC/MAIN.RS");
+        let fence = parse_content(
+            "### <file> Config.JSON </file>\n```json\n{}\n```\n",
+            Some(MdPatternType::FileFence),
+        );
+        assert_eq!(fence[0].path, "Config.JSON");
+    }
+
+    #[test]
+    fn test_executable_tag_in_headings() {
+        // Tagged headings are files whatever their extension.
+        let cases = [
+            (
+                "### scripts/run.sh [executable]\n```sh\necho\n```\n",
+                MdPatternType::HashMarker,
+            ),
+            (
+                "### <file> scripts/run.sh </file> [executable]\n```sh\necho\n```\n",
+                MdPatternType::FileFence,
+            ),
+            (
+                "// file: scripts/run.sh [executable]\n```sh\necho\n```\n",
+                MdPatternType::Raw,
+            ),
+            (
+                "========\nscripts/run.sh [executable]\n========\n```sh\necho\n```\n",
+                MdPatternType::Delimiter,
+            ),
+            (
+                "<file> scripts/run.sh </file> [executable]\n<code>\necho\n</code>\n",
+                MdPatternType::FileCode,
+            ),
+            (
+                "<code path=\"scripts/run.sh\"> [executable]\necho\n</code>\n",
+                MdPatternType::CodeTag,
+            ),
+            (
+                "```sh path=scripts/run.sh [executable]\necho\n```\n",
+                MdPatternType::FencedInfoPath,
+            ),
+        ];
+        for (md, pattern) in cases {
+            let parsed = parse_content(md, Some(pattern));
+            assert_eq!(parsed.len(), 1, "{:?}", pattern);
+            assert_eq!(parsed[0].path, "scripts/run.sh", "{:?}", pattern);
+            assert!(parsed[0].executable, "{:?}", pattern);
+            assert_eq!(parsed[0].content, "echo", "{:?}", pattern);
+        }
+        let tagged = parse_content(
+            "### <file> src/main.rs </file> [executable]\n```rust\nfn main() {}\n```\n",
+            None,
+        );
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].path, "src/main.rs");
+        assert!(tagged[0].executable);
+        let plain = parse_content("### src/main.rs\n```rust\nfn main() {}\n```\n", None);
+        assert!(!plain[0].executable);
+        let untagged = parse_content(
+            "### <file> scripts/run.sh </file>\n```sh\necho\n```\n",
+            Some(MdPatternType::FileFence),
+        );
+        assert!(untagged.is_empty());
+    }
+
     #[test]
     fn test_hash_marker_no_closing_fence() {
         let md = indoc! {r#"
@@ -510,7 +1658,6 @@ mod tests {
         assert_eq!(parsed[0].path, "src/lib.rs");
         assert!(parsed[0].content.contains("println!(\"hello\")"));
     }
-    use super::*;
 
     #[test]
     fn test_parse_hash_marker() {
@@ -529,13 +1676,527 @@ fn main() {}
 key: value
 ```
         "###;
-        let result = parse_hash_marker(input);
+        let lines: Vec<&str> = input.lines().collect();
+        // Sub-parsers return the block verbatim; trimming happens in parse_content.
+        let ext = config_extensions(&ParseConfig::default());
+        let result: Vec<ParsedFile> = parse_hash_marker(&lines, &ext, false)
+            .into_iter()
+            .map(|m| m.file)
+            .collect();
         assert_eq!(result.len(), 3);
         assert_eq!(result[0].path, "Cargo.toml");
-        assert_eq!(result[0].content, "[package]\nname = \"test\"");
+        assert_eq!(result[0].content, "[package]\nname = \"test\"\n");
         assert_eq!(result[1].path, "src/main.rs");
-        assert_eq!(result[1].content, "fn main() {}");
+        assert_eq!(result[1].content, "fn main() {}\n");
         assert_eq!(result[2].path, "config.yaml");
-        assert_eq!(result[2].content, "key: value");
+        assert_eq!(result[2].content, "key: value\n");
+    }
+
+    #[test]
+    fn test_fallback_single_uses_first_fence() {
+        let md = indoc! {r#"
+            Here is the script you asked for:
+            ```python
+            print("hi")
+            ```
+            ```text
+            ignored
+            ```
+        "#};
+        assert!(parse_content(md, None).is_empty());
+        let file = parse_fallback_single(md, "main.py").unwrap();
+        assert_eq!(file.path, "main.py");
+        assert_eq!(file.content, "print(\"hi\")");
+
+        let prose = parse_fallback_single("just some notes", "notes.txt").unwrap();
+        assert_eq!(prose.content, "just some notes");
+        assert!(parse_fallback_single("   ", "empty.txt").is_none());
+    }
+
+    #[test]
+    fn test_merge_fences_under_heading() {
+        let md = indoc! {r#"
+            ### src/main.rs
+            ```rust
+            fn helper() {}
+            ```
+            Then add the entry point:
+            ```rust
+            fn main() { helper(); }
+            ```
+            ### src/lib.rs
+            ```rust
+            pub fn lib() {}
+            ```
+        "#};
+        let config = ParseConfig {
+            merge_fences_under_heading: true,
+            ..Default::default()
+        };
+        let parsed = parse_content_with_config(md, &config);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].path, "src/main.rs");
+        assert!(parsed[1].content.contains("fn helper() {}"));
+        assert!(parsed[1].content.contains("fn main() { helper(); }"));
+        assert!(!parsed[1].content.contains("entry point"));
+        assert_eq!(parsed[0].content, "pub fn lib() {}");
+
+        // Without the option only the first fence is kept.
+        let parsed = parse_content(md, None);
+        assert!(!parsed[1].content.contains("fn main()"));
+    }
+
+    #[test]
+    fn test_closing_fence_with_info_string() {
+        let md = indoc! {r#"
+            // file: src/a.rs
+            ```rust
+            pub fn a() {}
+            ```end
+            // file: src/b.rs
+            ```rust
+            pub fn b() {}
+            ``` rust (v2)
+            ### src/c.rs
+            ```rust
+            pub fn c() {}
+            ```c++
+        "#};
+        let parsed = parse_content(md, Some(MdPatternType::Raw));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].content, "pub fn a() {}");
+        assert_eq!(parsed[1].content, "pub fn b() {}");
+
+        let parsed = parse_content(md, Some(MdPatternType::HashMarker));
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].content, "pub fn c() {}");
+    }
+
+    #[test]
+    fn test_split_on_h1_ignores_fenced_comments() {
+        let md = indoc! {r#"
+            Intro text.
+            # alpha
+            ### src/main.rs
+            ```toml
+            # not a heading
+            ```
+            # beta
+            ### src/lib.rs
+            ```rust
+            pub fn beta() {}
+            ```
+        "#};
+        let sections = split_on_h1(md);
+        let names: Vec<_> = sections.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["alpha", "beta"]);
+        assert!(sections[0].1.contains("# not a heading"));
+        assert!(!sections[0].1.contains("Intro text."));
+        assert!(split_on_h1("### src/main.rs\n").is_empty());
+    }
+
+    #[test]
+    fn test_fenced_info_path_pattern() {
+        let md = indoc! {r#"
+            ```toml path=Cargo.toml
+            [package]
+            name = "demo"
+            ```
+
+            ````markdown path="docs/usage.md"
+            Run it like this:
+            ```sh
+            cargo run
+            ```
+            Done.
+            ````
+        "#};
+        let parsed = parse_content(md, Some(MdPatternType::FencedInfoPath));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].path, "Cargo.toml");
+        assert_eq!(parsed[0].content, "[package]\nname = \"demo\"");
+        assert_eq!(parsed[1].path, "docs/usage.md");
+        assert_eq!(
+            parsed[1].content,
+            "Run it like this:\n```sh\ncargo run\n```\nDone."
+        );
+    }
+
+    #[test]
+    fn test_auto_merge_matches_union_of_forced_patterns() {
+        let md = indoc! {r#"
+            <code path="Cargo.toml">
+            [package]
+            name = "mixed"
+            </code>
+
+            ### src/main.rs
+            ```rust
+            fn main() {}
+            ```
+
+            ========
+            src/lib.rs
+            ========
+            ```rust
+            pub fn lib() {}
+            ```
+
+            // file: src/utils.rs
+            ```rust
+            pub fn util() {}
+            ```
+
+            ```rust path=src/info.rs
+            pub fn info() {}
+            ```
+        "#};
+        let patterns = [
+            MdPatternType::CodeTag,
+            MdPatternType::HashMarker,
+            MdPatternType::Delimiter,
+            MdPatternType::Raw,
+            MdPatternType::FileCode,
+            MdPatternType::FileFence,
+            MdPatternType::FencedInfoPath,
+        ];
+        let mut union: Vec<ParsedFile> = patterns
+            .iter()
+            .flat_map(|p| parse_content(md, Some(*p)))
+            .collect();
+        union.sort_by(|a, b| a.path.cmp(&b.path));
+        union.dedup_by(|a, b| a.path == b.path);
+
+        let merged = parse_content(md, None);
+        assert_eq!(merged, union);
+        assert_eq!(merged.len(), 5);
+    }
+
+    #[test]
+    fn test_extension_regexes_compiled_once_per_set() {
+        let extensions = vec!["py".to_string(), "cfg".to_string()];
+        let first = extension_regexes(&extensions);
+        let second = extension_regexes(&extensions);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let other = extension_regexes(&["py".to_string()]);
+        assert!(!Arc::ptr_eq(&first, &other));
+    }
+
+    #[test]
+    fn test_default_extension_regexes_shared() {
+        let first = config_extensions(&ParseConfig::default());
+        let second = config_extensions(&ParseConfig::default());
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_code_tag_uses_configured_extensions() {
+        let md = "<code path=\"app/main.py\">\nprint(1)\n</code>\n";
+        assert!(parse_content(md, Some(MdPatternType::CodeTag)).is_empty());
+
+        let extensions = vec!["py".to_string()];
+        let files = parse_content_with_extensions(md, Some(MdPatternType::CodeTag), &extensions);
+        assert_eq!(files, vec![ParsedFile::new("app/main.py", "print(1)")]);
+    }
+
+    #[test]
+    fn test_parse_content_with_extensions() {
+        let md = "// file: app/main.py\n```python\nprint(1)\n```\n";
+        assert!(parse_content(md, Some(MdPatternType::Raw)).is_empty());
+
+        let extensions = vec!["py".to_string()];
+        let files = parse_content_with_extensions(md, Some(MdPatternType::Raw), &extensions);
+        assert_eq!(
+            files,
+            vec![ParsedFile {
+                path: "app/main.py".to_string(),
+                content: "print(1)".to_string(),
+                language: None,
+                executable: false,
+            }]
+        );
+        let again = parse_content_with_extensions(md, Some(MdPatternType::Raw), &extensions);
+        assert_eq!(again, files);
+    }
+
+    #[test]
+    fn test_count_blocks() {
+        let md = indoc! {r#"
+            ### src/main.rs
+            ```rust
+            fn main() {}
+            ```
+
+            ### src/lib.rs
+            ```rust
+            pub fn lib() {}
+            ```
+
+            <code path="Cargo.toml">
+            [package]
+            </code>
+        "#};
+        assert_eq!(count_blocks(md, &ParseConfig::default()), 3);
+        let forced = ParseConfig {
+            forced: vec![MdPatternType::HashMarker],
+            ..Default::default()
+        };
+        assert_eq!(count_blocks(md, &forced), 2);
+        assert_eq!(count_blocks("no code here", &ParseConfig::default()), 0);
+    }
+
+    /// Records every log message so tests can assert on emitted warnings.
+    struct CaptureLogger;
+
+    static CAPTURED: std::sync::Mutex<Vec<(log::Level, String)>> =
+        std::sync::Mutex::new(Vec::new());
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            CAPTURED
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_stray_closing_fence_logs_warning() {
+        static LOGGER: CaptureLogger = CaptureLogger;
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let md = "### src/warned.rs\n```rust\nfn warned() {}\n```trailing-info\n";
+        let files = parse_content(md, Some(MdPatternType::HashMarker));
+        assert_eq!(files.len(), 1);
+
+        let captured = CAPTURED.lock().unwrap();
+        assert!(captured.iter().any(|(level, msg)| *level == log::Level::Warn
+            && msg.contains("\"trailing-info\"")));
+    }
+
+    #[test]
+    fn test_trim_mode_keeps_leading_indentation() {
+        let md = "### app/main.py\n```python\n    indented()\nlast()  \n\n```\n";
+        let parse = |trim_mode| {
+            let config = ParseConfig {
+                forced: vec![MdPatternType::HashMarker],
+                trim_mode,
+                ..Default::default()
+            };
+            parse_content_with_config(md, &config).remove(0).content
+        };
+        assert_eq!(parse(TrimMode::Full), "indented()\nlast()");
+        assert_eq!(parse(TrimMode::Trailing), "    indented()\nlast()");
+        assert_eq!(parse(TrimMode::None), "    indented()\nlast()  \n\n");
+
+        let fenced = "```make path=Makefile\n\tcargo build\n```\n";
+        let config = ParseConfig {
+            trim_mode: TrimMode::None,
+            ..Default::default()
+        };
+        let files = parse_content_with_config(fenced, &config);
+        assert_eq!(files[0].content, "\tcargo build\n");
+    }
+
+    #[test]
+    fn test_dedent_strips_common_indentation() {
+        let md = "1. Add the entry point:\n\n    ### src/main.rs\n    ```rust\n    fn main() {\n        run();\n\n    }\n    ```\n";
+        let config = ParseConfig {
+            dedent: true,
+            ..Default::default()
+        };
+        let files = parse_content_with_config(md, &config);
+        assert_eq!(files[0].content, "fn main() {\n    run();\n\n}");
+
+        assert_eq!(dedent("\t\ta\n\t\t\tb\n  \n\t\tc\n"), "a\n\tb\n\nc\n");
+        assert_eq!(dedent("a\n  b\n"), "a\n  b\n");
+    }
+
+    #[test]
+    fn test_indented_fence_indentation_is_stripped() {
+        let md = "  ### src/lib.rs\n  ```rust\n  pub fn f() {\n      g();\n\n  }\n  ```\n";
+        let config = ParseConfig {
+            trim_mode: TrimMode::None,
+            ..Default::default()
+        };
+        let files = parse_content_with_config(md, &config);
+        assert_eq!(files[0].content, "pub fn f() {\n    g();\n\n}\n");
+
+        // Content that is not uniformly indented with the fence is left alone.
+        let ragged = "  ### src/lib.rs\n  ```rust\n  a\n b\n  ```\n";
+        let files = parse_content_with_config(ragged, &config);
+        assert_eq!(files[0].content, "  a\n b\n");
+    }
+
+    #[test]
+    fn test_residual_prose_skips_file_blocks() {
+        let md = "# Demo\n\nThe entry point stays tiny.\n\n### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+                  The manifest:\n<code path=\"Cargo.toml\">\n[package]\n</code>\nDone.\n";
+        assert_eq!(
+            residual_prose(md, &ParseConfig::default()),
+            "# Demo\n\nThe entry point stays tiny.\n\nThe manifest:\n\nDone."
+        );
+        assert_eq!(
+            residual_prose("### a.rs\n```\nx\n```", &ParseConfig::default()),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_dump_parse_tree_reports_hash_match() {
+        let md = "Intro\n\n### src/main.rs\n```rust\nfn main() {}\n```\n";
+        let dump = dump_parse_tree(md, &ParseConfig::default());
+        let hash: Vec<&PatternMatch> = dump
+            .iter()
+            .filter(|m| m.pattern == MdPatternType::HashMarker)
+            .collect();
+        assert_eq!(hash.len(), 1);
+        assert_eq!(hash[0].path, "src/main.rs");
+        assert_eq!(hash[0].first_line, "fn main() {}");
+        assert_eq!(
+            &md[hash[0].span.clone()],
+            "### src/main.rs\n```rust\nfn main() {}\n```"
+        );
+    }
+
+    #[test]
+    fn test_forced_pattern_list_in_priority_order() {
+        let md = indoc! {r#"
+            ### src/main.rs
+            ```rust
+            fn main() { hash(); }
+            ```
+
+            <code path="src/main.rs">
+            fn main() { tag(); }
+            </code>
+
+            <code path="Cargo.toml">
+            [package]
+            </code>
+
+            // file: src/raw.rs
+            ```rust
+            pub fn raw() {}
+            ```
+        "#};
+        let config = ParseConfig {
+            forced: vec![MdPatternType::HashMarker, MdPatternType::CodeTag],
+            ..Default::default()
+        };
+        let files = parse_content_with_config(md, &config);
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/main.rs", "Cargo.toml"]);
+        assert_eq!(files[0].content, "fn main() { hash(); }");
+
+        let reversed = ParseConfig {
+            forced: vec![MdPatternType::CodeTag, MdPatternType::HashMarker],
+            ..Default::default()
+        };
+        let files = parse_content_with_config(md, &reversed);
+        assert_eq!(files[0].content, "fn main() { tag(); }");
+        assert_eq!(files.len(), 2);
+    }
+
+    #[test]
+    fn test_known_basenames_are_recognised() {
+        let md = "### Dockerfile\n```dockerfile\nFROM rust:1\n```\n";
+        let files = parse_content(md, Some(MdPatternType::HashMarker));
+        assert_eq!(files[0].path, "Dockerfile");
+        assert_eq!(files[0].content, "FROM rust:1");
+
+        let md = "// file: tools/Makefile\n```\nall:\n\tcargo build\n```\n\n\
+                  <code path=\"Gemfile\">\nsource \"https://rubygems.org\"\n</code>\n";
+        let paths: Vec<_> = parse_content(md, None)
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(paths, ["Gemfile", "tools/Makefile"]);
+
+        assert_eq!(known_basename_lang("docker/Dockerfile"), Some("dockerfile"));
+        assert_eq!(known_basename_lang("makefile.rs"), None);
+    }
+
+    #[test]
+    fn test_lang_directive_sets_language() {
+        let md = "<!-- lang: toml -->\n### build/config.rs\n```rust\n[settings]\n```\n\n\
+                  ### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+                  <!-- lang: text -->\n\n<code path=\"notes.rs\">\nplain\n</code>\n";
+        let files = parse_content(md, None);
+        let language = |path: &str| {
+            files
+                .iter()
+                .find(|f| f.path == path)
+                .and_then(|f| f.language.clone())
+        };
+        assert_eq!(language("build/config.rs").as_deref(), Some("toml"));
+        assert_eq!(language("src/main.rs"), None);
+        assert_eq!(language("notes.rs").as_deref(), Some("text"));
+    }
+
+    #[test]
+    fn test_detect_best_pattern() {
+        let md = "### src/main.rs\n```rust\nfn main() {}\n```\n\n### src/lib.rs\n```rust\n```\n\n\
+                  <code path=\"Cargo.toml\">\n[package]\n</code>\n";
+        assert_eq!(
+            detect_best_pattern(md, &ParseConfig::default()),
+            Some((MdPatternType::HashMarker, 2))
+        );
+        assert_eq!(
+            detect_best_pattern("just prose", &ParseConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_best_pattern_counts_duplicate_paths_once() {
+        let md = "### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+                  ### src/main.rs\n```rust\nfn main() { run() }\n```\n";
+        let config = ParseConfig::default();
+        assert_eq!(
+            detect_best_pattern(md, &config),
+            Some((MdPatternType::HashMarker, count_blocks(md, &config)))
+        );
+        assert_eq!(count_blocks(md, &config), 1);
+    }
+
+    #[test]
+    fn test_hash_heading_with_label_prefix() {
+        let md = indoc! {r#"
+            ### 📄 src/main.rs
+            ```rust
+            fn main() {}
+            ```
+
+            ### File: `src/lib.rs`
+            ```rust
+            pub fn lib() {}
+            ```
+
+            ## File: Cargo.toml
+            ```toml
+            [package]
+            ```
+        "#};
+        let files = parse_content(md, Some(MdPatternType::HashMarker));
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/main.rs", "src/lib.rs", "Cargo.toml"]);
+        assert_eq!(files[1].content, "pub fn lib() {}");
+
+        // A label form needs a known extension or file name; other headings with a
+        // space in them are prose.
+        let md = "## Version 1.2\n```text\nnotes\n```\n### Step 2.1\n```\nrun\n```\n\
+                  ### File: Dockerfile\n```\nFROM x\n```\n";
+        let files = parse_content(md, Some(MdPatternType::HashMarker));
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["Dockerfile"]);
     }
 }
@@ -1,33 +1,282 @@
+use crate::parser::{self, ParseConfig};
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use rayon::prelude::*;
 
-/// Finds all markdown files in the given directory that match the pattern `{name}.md`
+/// Extensions recognised as Markdown input when none are configured.
+pub const DEFAULT_MD_EXTENSIONS: &[&str] = &["md", "markdown", "mdx"];
+
+/// Finds all markdown files in the given directory, i.e. `{name}.md`, `{name}.markdown`
+/// or `{name}.mdx`.
+///
+/// The result is sorted by path so that processing order and any derived output
+/// are reproducible regardless of the filesystem's `read_dir` order.
 pub fn find_md_files(dir: &Path) -> Vec<PathBuf> {
+    let defaults: Vec<String> = DEFAULT_MD_EXTENSIONS
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    find_md_files_with_extensions(dir, &defaults)
+}
+
+/// Same as [`find_md_files`], matching any of `extensions` (without the dot,
+/// case-insensitively) instead of the defaults.
+pub fn find_md_files_with_extensions(dir: &Path, extensions: &[String]) -> Vec<PathBuf> {
     let mut md_files = Vec::new();
     if let Ok(entries) = fs::read_dir(dir) {
-        entries.filter_map(|entry| entry.ok())
-            .for_each(|entry| {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(ext) = path.extension() {
-                        if ext == "md" {
-                            md_files.push(path);
-                        }
-                    }
-                }
-            });
+        entries.filter_map(|entry| entry.ok()).for_each(|entry| {
+            let path = entry.path();
+            if path.is_file() && has_extension(&path, extensions) {
+                md_files.push(path);
+            }
+        });
     }
+    md_files.sort();
     md_files
 }
 
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        })
+}
+
 /// Reads the entire content of the specified file.
 pub fn read_file(path: &Path) -> io::Result<String> {
     fs::read_to_string(path)
 }
 
-/// Extracts the project name from the markdown file's filename (without extension).
+/// Extracts the project name from the markdown file's filename without its (last)
+/// extension, so `spec.markdown` and `spec.mdx` both name `spec`.
 pub fn extract_project_name(path: &Path) -> Option<String> {
-    path.file_stem().and_then(|os_str| os_str.to_str()).map(|s| s.to_string())
+    path.file_stem()
+        .and_then(|os_str| os_str.to_str())
+        .map(|s| s.to_string())
+}
+
+/// Makes a derived project name safe as a folder name: whitespace becomes `_`,
+/// characters other than ASCII letters, digits, `_`, `-` and `.` are dropped, and
+/// runs of `_` are collapsed, so `response (2)` becomes `response_2`. A name with
+/// nothing safe in it, or that comes out as `.` or `..`, becomes `project`.
+pub fn sanitize_project_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        let c = if c.is_whitespace() { '_' } else { c };
+        if !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+            continue;
+        }
+        if c == '_' && out.ends_with('_') {
+            continue;
+        }
+        out.push(c);
+    }
+    let out = out.trim_matches('_');
+    if matches!(out, "" | "." | "..") {
+        "project".to_string()
+    } else {
+        out.to_string()
+    }
+}
+
+/// Whether `name` can be used as-is as one folder under the output directory:
+/// non-empty, not `.` or `..`, and free of `/` and `\`, so it can neither
+/// nest folders nor climb out of the output directory.
+pub fn is_safe_project_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Where the name of a generated project comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NameSource {
+    /// The Markdown file's stem.
+    #[default]
+    Filename,
+    /// The first top-level `# Name` heading.
+    H1,
+    /// The `name` of the `[package]` in the document's root `Cargo.toml` block.
+    Cargo,
+}
+
+/// Derives the project name for a Markdown file according to `source`, falling
+/// back to the file stem when the heading or `Cargo.toml` name is missing or is
+/// not a single folder name (see [`is_safe_project_name`]).
+pub fn project_name(
+    path: &Path,
+    content: &str,
+    source: NameSource,
+    config: &ParseConfig,
+) -> Option<String> {
+    let name = match source {
+        NameSource::Filename => None,
+        NameSource::H1 => parser::split_on_h1(content)
+            .into_iter()
+            .next()
+            .map(|(name, _)| name),
+        NameSource::Cargo => parser::parse_content_with_config(content, config)
+            .iter()
+            .find(|file| file.path == "Cargo.toml")
+            .and_then(|file| cargo_package_name(&file.content)),
+    };
+    match name {
+        Some(name) if is_safe_project_name(&name) => return Some(name),
+        Some(name) => log::warn!(
+            "{:?} project name {:?} in {:?} is not a single folder name; using the file name",
+            source,
+            name,
+            path
+        ),
+        None if source != NameSource::Filename => log::warn!(
+            "No {:?} project name found in {:?}; using the file name",
+            source,
+            path
+        ),
+        None => {}
+    }
+    extract_project_name(path)
+}
+
+/// Reads `name = "..."` from the `[package]` table of a `Cargo.toml`.
+fn cargo_package_name(manifest: &str) -> Option<String> {
+    lazy_static! {
+        static ref TABLE_REGEX: Regex = Regex::new(r"^\s*\[+\s*([^\]]+?)\s*\]").unwrap();
+        static ref NAME_REGEX: Regex = Regex::new(r#"^\s*name\s*=\s*"([^"]+)""#).unwrap();
+    }
+    let mut in_package = false;
+    for line in manifest.lines() {
+        if let Some(cap) = TABLE_REGEX.captures(line) {
+            in_package = &cap[1] == "package";
+        } else if in_package && let Some(cap) = NAME_REGEX.captures(line) {
+            return Some(cap[1].to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_md_files_sorted() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in ["zeta.md", "alpha.md", "mid.md", "notes.txt"] {
+            fs::write(tmp.path().join(name), "").unwrap();
+        }
+
+        let first = find_md_files(tmp.path());
+        let second = find_md_files(tmp.path());
+        assert_eq!(first, second, "Expected a stable order across calls");
+
+        let names: Vec<_> = first
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["alpha.md", "mid.md", "zeta.md"]);
+    }
+
+    #[test]
+    fn test_find_md_files_with_other_extensions() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in ["a.md", "b.markdown", "c.MDX", "d.txt", "e.markdown.bak"] {
+            fs::write(tmp.path().join(name), "").unwrap();
+        }
+        let names = |files: Vec<PathBuf>| -> Vec<String> {
+            files
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+                .collect()
+        };
+        assert_eq!(
+            names(find_md_files(tmp.path())),
+            ["a.md", "b.markdown", "c.MDX"]
+        );
+        assert_eq!(
+            names(find_md_files_with_extensions(
+                tmp.path(),
+                &["markdown".to_string()]
+            )),
+            ["b.markdown"]
+        );
+        assert_eq!(
+            extract_project_name(&tmp.path().join("b.markdown")).as_deref(),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn test_sanitize_project_name() {
+        assert_eq!(sanitize_project_name("response (2)"), "response_2");
+        assert_eq!(sanitize_project_name("My  Demo: v1.2"), "My_Demo_v1.2");
+        assert_eq!(sanitize_project_name("already_ok-1"), "already_ok-1");
+        assert_eq!(sanitize_project_name("(?)"), "project");
+        assert_eq!(sanitize_project_name(".."), "project");
+        assert_eq!(sanitize_project_name(" . "), "project");
+        assert!(is_safe_project_name(&sanitize_project_name("../../up")));
+    }
+
+    const DOC: &str = "Intro text.\n\n\
+        # trait_enforcement_demo\n\n\
+        ### Cargo.toml\n```toml\n[workspace]\nname = \"not_this\"\n\n\
+        [package]\nname = \"cargo_demo\"\nversion = \"0.1.0\"\n```\n\n\
+        ### src/main.rs\n```rust\nfn main() {}\n```\n";
+
+    #[test]
+    fn test_project_name_from_filename() {
+        let path = Path::new("docs/response.md");
+        let name = project_name(path, DOC, NameSource::Filename, &ParseConfig::default());
+        assert_eq!(name.as_deref(), Some("response"));
+    }
+
+    #[test]
+    fn test_project_name_from_h1() {
+        let path = Path::new("response.md");
+        let name = project_name(path, DOC, NameSource::H1, &ParseConfig::default());
+        assert_eq!(name.as_deref(), Some("trait_enforcement_demo"));
+
+        let no_heading = project_name(path, "no heading", NameSource::H1, &ParseConfig::default());
+        assert_eq!(no_heading.as_deref(), Some("response"));
+
+        for heading in ["# ../../escaped", "# Setup / Install", "# ..", "# a\\b"] {
+            let doc = format!(
+                "{}\n\n### src/main.rs\n```rust\nfn main() {{}}\n```\n",
+                heading
+            );
+            let name = project_name(path, &doc, NameSource::H1, &ParseConfig::default());
+            assert_eq!(name.as_deref(), Some("response"), "{}", heading);
+        }
+    }
+
+    #[test]
+    fn test_project_name_from_cargo() {
+        let path = Path::new("response.md");
+        let name = project_name(path, DOC, NameSource::Cargo, &ParseConfig::default());
+        assert_eq!(name.as_deref(), Some("cargo_demo"));
+
+        let no_manifest = "### src/lib.rs\n```rust\npub fn f() {}\n```\n";
+        let name = project_name(
+            path,
+            no_manifest,
+            NameSource::Cargo,
+            &ParseConfig::default(),
+        );
+        assert_eq!(name.as_deref(), Some("response"));
+
+        let escaping = "### Cargo.toml\n```toml\n[package]\nname = \"../up\"\n```\n";
+        let name = project_name(path, escaping, NameSource::Cargo, &ParseConfig::default());
+        assert_eq!(name.as_deref(), Some("response"));
+    }
+
+    #[test]
+    fn test_is_safe_project_name() {
+        assert!(is_safe_project_name("demo_1.2"));
+        for name in ["", ".", "..", "a/b", "/abs", "..\\up"] {
+            assert!(!is_safe_project_name(name), "{:?}", name);
+        }
+    }
 }
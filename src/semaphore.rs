@@ -0,0 +1,90 @@
+use std::sync::{Condvar, Mutex};
+
+/// Minimal counting semaphore used to bound how many tasks of the rayon loop
+/// may run a given stage at the same time.
+pub struct Semaphore {
+    permits: Mutex<usize>,
+    capacity: usize,
+    available: Condvar,
+}
+
+/// Releases its permits back to the semaphore when dropped.
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+}
+
+impl Semaphore {
+    /// Creates a semaphore with `permits` slots (at least one).
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: Mutex::new(permits.max(1)),
+            capacity: permits.max(1),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Blocks until a slot is free and returns a guard holding it.
+    pub fn acquire(&self) -> Permit<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Blocks until `count` slots are free and returns a guard holding them.
+    ///
+    /// Requests larger than the whole semaphore are clamped to its capacity, so an
+    /// oversized task still runs, just alone.
+    pub fn acquire_many(&self, count: usize) -> Permit<'_> {
+        let count = count.clamp(1, self.capacity);
+        let mut permits = self.permits.lock().unwrap();
+        while *permits < count {
+            permits = self.available.wait(permits).unwrap();
+        }
+        *permits -= count;
+        Permit {
+            semaphore: self,
+            count,
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.semaphore.permits.lock().unwrap() += self.count;
+        self.semaphore.available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_acquire_many_bounds_in_flight_weight() {
+        // A budget of 250 bytes admits at most two 100-byte files at a time.
+        let budget = Semaphore::new(250);
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(8)
+            .build()
+            .unwrap();
+
+        pool.install(|| {
+            (0..16).into_par_iter().for_each(|_| {
+                let _permit = budget.acquire_many(100);
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(10));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            });
+        });
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        // An oversized request is clamped and still completes.
+        drop(budget.acquire_many(10_000));
+    }
+}
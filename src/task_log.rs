@@ -0,0 +1,63 @@
+use log::Level;
+use std::cell::RefCell;
+use std::sync::Mutex;
+
+/// Held while a buffered log is written, so blocks from different tasks never interleave.
+static FLUSH_LOCK: Mutex<()> = Mutex::new(());
+
+/// Log output of one processing task.
+///
+/// Unbuffered logs emit each record immediately. Buffered logs collect the records
+/// and emit them as one contiguous block when flushed or dropped.
+pub struct TaskLog {
+    buffer: Option<RefCell<Vec<(Level, String)>>>,
+}
+
+impl TaskLog {
+    pub fn new(buffered: bool) -> Self {
+        Self {
+            buffer: buffered.then(|| RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn info(&self, line: String) {
+        self.record(Level::Info, line);
+    }
+
+    pub fn warn(&self, line: String) {
+        self.record(Level::Warn, line);
+    }
+
+    pub fn error(&self, line: String) {
+        self.record(Level::Error, line);
+    }
+
+    fn record(&self, level: Level, line: String) {
+        match &self.buffer {
+            Some(buffer) => buffer.borrow_mut().push((level, line)),
+            None => log::log!(level, "{}", line),
+        }
+    }
+
+    /// Emits everything buffered so far.
+    pub fn flush(&self) {
+        let Some(buffer) = &self.buffer else {
+            return;
+        };
+        let lines = buffer.take();
+        if lines.is_empty() {
+            return;
+        }
+        let _guard = FLUSH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for (level, line) in lines {
+            log::log!(level, "{}", line);
+        }
+        log::logger().flush();
+    }
+}
+
+impl Drop for TaskLog {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
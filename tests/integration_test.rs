@@ -23,12 +23,9 @@ fn integration_test_generate_project() {
     "#;
     fs::write(&md_path, md_content).unwrap();
 
-    // Change working directory.
-    let orig_dir = std::env::current_dir().unwrap();
-    std::env::set_current_dir(tmp_dir.path()).unwrap();
-
-    // Run the application binary.
-    let mut cmd = Command::cargo_bin("prk_md_parser").unwrap();
+    // Run the application binary from inside the temporary directory.
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path());
     cmd.assert().success();
 
     // Check that the output folder was created.
@@ -36,6 +33,536 @@ fn integration_test_generate_project() {
     assert!(Path::new(&output_path.join("Cargo.toml")).exists());
     assert!(Path::new(&output_path.join("src/main.rs")).exists());
 
-    // Restore original working directory.
-    std::env::set_current_dir(orig_dir).unwrap();
-}
\ No newline at end of file
+    // The indented fence's indentation is stripped, keeping the code's own nesting.
+    let main_rs = fs::read_to_string(output_path.join("src/main.rs")).unwrap();
+    assert!(main_rs.starts_with("fn main()"), "got {:?}", main_rs);
+    assert_eq!(main_rs, "fn main() {\n    println!(\"Hello, Demo!\");\n}");
+}
+
+#[test]
+fn integration_test_fallback_single() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "Here is the script:\n\n```python\nprint(\"hi\")\n```\n";
+    fs::write(tmp_dir.path().join("script.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--fallback-single", "main.py"]);
+    cmd.assert().success();
+
+    let written = tmp_dir.path().join("output/script/main.py");
+    assert_eq!(fs::read_to_string(written).unwrap(), "print(\"hi\")");
+}
+
+#[test]
+fn integration_test_exit_code_no_input() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path());
+    cmd.assert().code(2);
+}
+
+#[test]
+fn integration_test_exit_code_generation_error() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "### src/main.rs\n```rust\nfn main() {}\n```\n";
+    fs::write(tmp_dir.path().join("demo.md"), md_content).unwrap();
+    // A regular file where the output directory should go makes generation fail.
+    fs::write(tmp_dir.path().join("blocked"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).args(["-o", "blocked"]);
+    cmd.assert().code(4);
+}
+
+#[test]
+fn integration_test_exit_code_strict_empty() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(tmp_dir.path().join("notes.md"), "Just prose, no code.\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path());
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--strict");
+    cmd.assert().code(3);
+}
+
+#[test]
+fn integration_test_fail_fast_stops_after_first_failure() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "### src/main.rs\n```rust\nfn main() {}\n```\n";
+    for name in ["a.md", "b.md", "c.md"] {
+        fs::write(tmp_dir.path().join(name), md_content).unwrap();
+    }
+    // Block the first project's output directory so its generation fails.
+    fs::create_dir_all(tmp_dir.path().join("output")).unwrap();
+    fs::write(tmp_dir.path().join("output/a"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .env("RAYON_NUM_THREADS", "1")
+        .arg("--fail-fast");
+    cmd.assert().code(4);
+    assert!(!tmp_dir.path().join("output/b").exists());
+    assert!(!tmp_dir.path().join("output/c").exists());
+
+    // Without --fail-fast the remaining projects are still generated.
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .env("RAYON_NUM_THREADS", "1");
+    cmd.assert().code(4);
+    assert!(tmp_dir.path().join("output/b/src/main.rs").exists());
+    assert!(tmp_dir.path().join("output/c/src/main.rs").exists());
+}
+
+#[test]
+fn integration_test_split_on_h1() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "# alpha\n\n### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+                      # beta\n\n### src/lib.rs\n```rust\npub fn beta() {}\n```\n";
+    fs::write(tmp_dir.path().join("projects.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--split-on-h1");
+    cmd.assert().success();
+
+    let output = tmp_dir.path().join("output");
+    assert!(output.join("alpha/src/main.rs").exists());
+    assert!(output.join("beta/src/lib.rs").exists());
+    assert!(!output.join("alpha/src/lib.rs").exists());
+    assert!(!output.join("projects").exists());
+}
+
+#[test]
+fn integration_test_split_on_h1_rejects_unsafe_section_names() {
+    for heading in ["# a/b", "# .."] {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let md_content = format!(
+            "# alpha\n\n### src/main.rs\n```rust\nfn main() {{}}\n```\n\n\
+             {}\n\n### src/lib.rs\n```rust\npub fn lib() {{}}\n```\n",
+            heading
+        );
+        fs::write(tmp_dir.path().join("projects.md"), md_content).unwrap();
+
+        let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+        cmd.current_dir(tmp_dir.path()).arg("--split-on-h1");
+        let stderr = cmd.assert().code(3).get_output().stderr.clone();
+        assert!(
+            String::from_utf8(stderr)
+                .unwrap()
+                .contains("is not a single folder name"),
+            "{}",
+            heading
+        );
+        assert!(!tmp_dir.path().join("output/alpha").exists());
+        assert!(!tmp_dir.path().join("output/a").exists());
+        assert!(!tmp_dir.path().join("src").exists());
+    }
+}
+
+#[test]
+fn integration_test_max_blocks_counts_every_h1_section() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "# alpha\n\n### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+                      # beta\n\n### src/lib.rs\n```rust\npub fn beta() {}\n```\n\n\
+                      ### src/util.rs\n```rust\npub fn util() {}\n```\n";
+    fs::write(tmp_dir.path().join("projects.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--split-on-h1", "--max-blocks", "2"]);
+    let output = cmd.assert().code(3).get_output().stderr.clone();
+    assert!(
+        String::from_utf8(output)
+            .unwrap()
+            .contains("3 file blocks exceed the limit of 2")
+    );
+    assert!(!tmp_dir.path().join("output/alpha").exists());
+    assert!(!tmp_dir.path().join("output/beta").exists());
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--split-on-h1", "--max-blocks", "3"]);
+    cmd.assert().success();
+    assert!(tmp_dir.path().join("output/beta/src/util.rs").exists());
+}
+
+#[test]
+fn integration_test_ordered_output_groups_lines_per_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let names: Vec<String> = (0..8).map(|i| format!("p{}", i)).collect();
+    for name in &names {
+        let md_content = "### src/main.rs\n```rust\nfn main() {}\n```\n";
+        fs::write(tmp_dir.path().join(format!("{}.md", name)), md_content).unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .env("RAYON_NUM_THREADS", "4")
+        .arg("--ordered-output");
+    let output = cmd.assert().success().get_output().stderr.clone();
+    let stderr = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+
+    for name in &names {
+        let start = lines
+            .iter()
+            .position(|l| l.starts_with("Processing file:") && l.contains(&format!("{}.md", name)))
+            .unwrap();
+        assert!(lines[start + 1].starts_with(&format!("Project {} generated", name)));
+    }
+}
+
+#[test]
+fn integration_test_max_memory_bounds_files_in_flight() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "### src/main.rs\n```rust\nfn main() {}\n```\n";
+    for i in 0..8 {
+        fs::write(tmp_dir.path().join(format!("p{}.md", i)), md_content).unwrap();
+    }
+
+    // The budget fits one file but not two, so files are read one at a time.
+    let budget = md_content.len() * 3 / 2;
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .env("RAYON_NUM_THREADS", "4")
+        .env("RUST_LOG", "prk_mdgen=debug")
+        .args(["--max-memory", &budget.to_string()]);
+    let output = cmd.assert().success().get_output().stderr.clone();
+    let stderr = String::from_utf8(output).unwrap();
+
+    let (mut in_flight, mut peak, mut reserved) = (0, 0, 0);
+    for line in stderr.lines() {
+        if line.contains("Reserved") && line.contains("--max-memory") {
+            in_flight += 1;
+            reserved += 1;
+            peak = peak.max(in_flight);
+        } else if line.contains("Released the --max-memory reservation") {
+            in_flight -= 1;
+        }
+    }
+    assert_eq!(reserved, 8);
+    assert_eq!(in_flight, 0);
+    assert_eq!(peak, 1);
+}
+
+#[test]
+fn integration_test_count_only() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+                      ### src/lib.rs\n```rust\npub fn lib() {}\n```\n";
+    fs::write(tmp_dir.path().join("demo.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--count-only");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let stdout = String::from_utf8(output).unwrap();
+    assert!(stdout.contains("demo.md: 2 blocks"));
+    assert!(stdout.contains("Total: 2 blocks"));
+    assert!(!tmp_dir.path().join("output").exists());
+}
+
+#[test]
+fn integration_test_extract_since_mtime() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(tmp_dir.path().join("src")).unwrap();
+    fs::write(tmp_dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["extract", "--since-mtime", "1h", "-o", "docs"]);
+    cmd.assert().success();
+    let md = fs::read_to_string(tmp_dir.path().join("docs/codebase.md")).unwrap();
+    assert!(md.contains("### <file> src/main.rs </file>"));
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["extract", "--since-mtime", "5y"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn integration_test_dump_parse_tree() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "### src/main.rs\n```rust\nfn main() {}\n```\n";
+    fs::write(tmp_dir.path().join("demo.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--dump-parse-tree", "demo.md"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let stdout = String::from_utf8(output).unwrap();
+    assert!(stdout.contains("HashMarker:\n  src/main.rs  bytes 0..40  fn main() {}"));
+    assert!(!tmp_dir.path().join("output").exists());
+}
+
+#[test]
+fn integration_test_dedent() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "1. Create the entry point:\n\n   ### src/main.rs\n   ```rust\n   fn main() {\n       println!(\"hi\");\n   }\n   ```\n";
+    fs::write(tmp_dir.path().join("nested.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--dedent");
+    cmd.assert().success();
+
+    let written = fs::read_to_string(tmp_dir.path().join("output/nested/src/main.rs")).unwrap();
+    assert_eq!(written, "fn main() {\n    println!(\"hi\");\n}");
+}
+
+#[test]
+fn integration_test_summary_only() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp_dir.path().join("one.md"),
+        "### src/main.rs\n```rust\nfn main() {}\n```\n\n### Cargo.toml\n```toml\n[package]\n```\n",
+    )
+    .unwrap();
+    fs::write(
+        tmp_dir.path().join("two.md"),
+        "### src/lib.rs\n```rust\npub fn f() {}\n```\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--summary-only");
+    let output = cmd.assert().success().get_output().clone();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(
+        stdout,
+        "2 Markdown files, 2 projects generated, 3 files written, 0 errors\n"
+    );
+    assert!(!stderr.contains("Processing file"), "stderr: {}", stderr);
+}
+
+#[test]
+fn integration_test_keep_prose() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let md_content = "# Design\n\nState lives in one struct.\n\n### src/main.rs\n```rust\nfn main() {}\n```\n\nRun it with cargo.\n";
+    fs::write(tmp_dir.path().join("design.md"), md_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--keep-prose");
+    cmd.assert().success();
+
+    let description =
+        fs::read_to_string(tmp_dir.path().join("output/design/DESCRIPTION.md")).unwrap();
+    assert_eq!(
+        description,
+        "# Design\n\nState lives in one struct.\n\nRun it with cargo.\n"
+    );
+}
+
+#[test]
+fn integration_test_detect_pattern() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp_dir.path().join("doc.md"),
+        "### src/main.rs\n```rust\nfn main() {}\n```\n\n### Cargo.toml\n```toml\n[package]\n```\n",
+    )
+    .unwrap();
+    fs::write(tmp_dir.path().join("prose.md"), "Nothing to see here.\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--detect-pattern", "doc.md"]);
+    cmd.assert().success().stdout("hash 2\n");
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--detect-pattern", "prose.md"]);
+    cmd.assert().code(3).stdout("");
+}
+
+#[test]
+fn integration_test_dump_config() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--dump-config");
+    let output = cmd.assert().success().get_output().clone();
+    let config: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(config["output_dir"], "output");
+    assert_eq!(config["exec_jobs"], 1);
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).args([
+        "--dump-config",
+        "-o",
+        "custom",
+        "--exec-jobs",
+        "4",
+        "-p",
+        "hash,code-tag",
+        "--content-filter",
+        "TODO",
+        "--note-exec",
+        "--binary",
+        "base64",
+    ]);
+    let output = cmd.assert().success().get_output().clone();
+    let config: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(config["output_dir"], "custom");
+    assert_eq!(config["exec_jobs"], 4);
+    assert_eq!(config["pattern"], serde_json::json!(["hash", "code-tag"]));
+    assert_eq!(config["content_filter"], "TODO");
+    assert_eq!(config["note_exec"], true);
+    assert_eq!(config["binary"], "base64");
+    assert_eq!(config["restore_exec"], false);
+    assert!(!tmp_dir.path().join("custom").exists());
+}
+
+#[test]
+fn integration_test_on_duplicate() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp_dir.path().join("dup.md"),
+        "### src/main.rs\n```rust\nfn main() {}\n```\n\n\
+         ### src/main.rs\n```rust\nfn helper() {}\n```\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--on-duplicate", "concatenate"]);
+    cmd.assert().success();
+    let main_rs = fs::read_to_string(tmp_dir.path().join("output/dup/src/main.rs")).unwrap();
+    assert_eq!(main_rs, "fn main() {}\nfn helper() {}");
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--on-duplicate", "error", "-o", "strict"]);
+    cmd.assert().code(3);
+    assert!(!tmp_dir.path().join("strict/dup").exists());
+}
+
+#[test]
+fn integration_test_auto_readme() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp_dir.path().join("greeter.md"),
+        "# Greeter\n\nPrints a friendly greeting.\n\n### src/main.rs\n```rust\nfn main() {}\n```\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--auto-readme");
+    cmd.assert().success();
+    let readme = fs::read_to_string(tmp_dir.path().join("output/greeter/README.md")).unwrap();
+    assert_eq!(readme, "Prints a friendly greeting.\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn integration_test_note_exec_round_trip() {
+    use std::os::unix::fs::PermissionsExt;
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let src = tmp_dir.path().join("src_project");
+    fs::create_dir_all(src.join("scripts")).unwrap();
+    fs::create_dir_all(src.join("src")).unwrap();
+    fs::write(src.join("src/main.rs"), "fn main() {}\n").unwrap();
+    let script = src.join("scripts/run.sh");
+    fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(&src)
+        .args(["extract", "--note-exec", "--skip", "!scripts", "-o"])
+        .arg(tmp_dir.path().join("gen"));
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path().join("gen"))
+        .arg("--restore-exec");
+    cmd.assert().success();
+
+    let project = tmp_dir.path().join("gen/output/codebase");
+    let mode = |rel: &str| {
+        fs::metadata(project.join(rel))
+            .unwrap()
+            .permissions()
+            .mode()
+    };
+    assert_ne!(mode("scripts/run.sh") & 0o111, 0);
+    assert_eq!(mode("src/main.rs") & 0o111, 0);
+    assert_eq!(
+        fs::read_to_string(project.join("scripts/run.sh")).unwrap(),
+        "#!/bin/sh\necho hi"
+    );
+    assert_eq!(fs::read_dir(project.join("scripts")).unwrap().count(), 1);
+    assert!(fs::read_dir(&project).unwrap().all(|entry| {
+        !entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .contains("<file>")
+    }));
+}
+
+#[test]
+fn integration_test_merge_into() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp_dir.path().join("core.md"),
+        "### src/main.rs\n```rust\nmod util;\nfn main() {}\n```\n",
+    )
+    .unwrap();
+    fs::write(
+        tmp_dir.path().join("util.md"),
+        "### src/util.rs\n```rust\npub fn helper() {}\n```\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path())
+        .args(["--merge-into", "app"]);
+    cmd.assert().success();
+
+    let project = tmp_dir.path().join("output/app");
+    assert!(
+        fs::read_to_string(project.join("src/main.rs"))
+            .unwrap()
+            .contains("mod util;")
+    );
+    assert!(
+        fs::read_to_string(project.join("src/util.rs"))
+            .unwrap()
+            .contains("pub fn helper() {}")
+    );
+    assert!(project.join("core.md").exists() && project.join("util.md").exists());
+    assert!(!tmp_dir.path().join("output/core").exists());
+}
+
+#[test]
+fn integration_test_parse_only() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp_dir.path().join("good.md"),
+        "### src/main.rs\n```rust\nfn main() {}\n```\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--parse-only");
+    let output = cmd.assert().success().get_output().clone();
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .ends_with("good.md: 1 blocks\n")
+    );
+    assert!(
+        !tmp_dir.path().join("output").exists(),
+        "nothing is written"
+    );
+
+    fs::write(tmp_dir.path().join("empty.md"), "No code here.\n").unwrap();
+    let mut cmd = Command::cargo_bin("prk_mdgen").unwrap();
+    cmd.current_dir(tmp_dir.path()).arg("--parse-only");
+    cmd.assert().code(3);
+    assert!(!tmp_dir.path().join("output").exists());
+}